@@ -1,54 +1,318 @@
-use shared::{Request, Response};
+use shared::{
+    ohlcv::{Bars, OHLCVList},
+    Encoding, Request, Response, Tagged, WorkerRequest, WorkerResponse, PROTOCOL_VERSION,
+};
 
-use futures::future;
+use futures::{stream, StreamExt};
 use lambda_runtime::{run, service_fn, tracing, Error, LambdaEvent};
 
-async fn handler(event: LambdaEvent<Vec<Request>>) -> Result<Vec<Response>, Error> {
-    let urls = event.payload.into_iter().map(|r| {
-        format!(
-            "https://api.geckoterminal.com/api/v2/networks/{network}/pools/{pool}/ohlcv/day?limit=1000",
-            network = r.network,
-            pool = r.pool_address
-        )
-    });
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 
-    let client = reqwest::Client::new();
-    let bodies = future::join_all(urls.into_iter().map(|url| {
-        let client = &client;
-        async move {
-            let resp = match client.get(url).send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    return Response {
-                        status: None,
-                        body: None,
-                        err: Some(e.to_string()),
-                    }
-                }
-            };
-            let code = resp.status().as_u16();
-            match resp.text().await {
-                Ok(body) => Response {
-                    status: Some(code),
-                    body: Some(body),
-                    err: None,
+/// Limits for the upstream requests (overridable through the function's environment).
+struct Config {
+    /// Timeout of each attempt (`FETCH_TIMEOUT_SECS`)
+    timeout: Duration,
+    /// Requests in flight at any time (`MAX_CONCURRENT_FETCHES`)
+    max_concurrent: usize,
+    /// Attempts after the first one for connection errors, timeouts and 5xx (`MAX_FETCH_RETRIES`)
+    max_retries: u8,
+    /// Delay before the first retry (doubled for each retry)
+    backoff: Duration,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        fn var<T: FromStr>(name: &str, default: T) -> T {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        }
+
+        Config {
+            timeout: Duration::from_secs(var("FETCH_TIMEOUT_SECS", 10)),
+            max_concurrent: var("MAX_CONCURRENT_FETCHES", 16usize).max(1),
+            max_retries: var("MAX_FETCH_RETRIES", 2),
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+async fn handler(
+    event: LambdaEvent<WorkerRequest>,
+    client: &reqwest::Client,
+    config: &Config,
+) -> Result<WorkerResponse, Error> {
+    let version = event.payload.version();
+    if version != PROTOCOL_VERSION {
+        let mut resp = WorkerResponse::new(vec![]);
+        resp.err = Some(format!(
+            "unsupported protocol version {} (worker has {})",
+            version, PROTOCOL_VERSION
+        ));
+        return Ok(resp);
+    }
+    let (requests, encoding, analyze) = match event.payload {
+        WorkerRequest::Handshake { .. } => return Ok(WorkerResponse::new(vec![])),
+        WorkerRequest::Fetch {
+            requests,
+            encoding,
+            analyze,
+            ..
+        } => (requests, encoding, analyze),
+    };
+
+    let responses = stream::iter(requests)
+        .map(|r| async move {
+            let resp = fetch(client, &r.inner, config).await;
+            Tagged {
+                id: r.id,
+                inner: if analyze {
+                    signals(resp, r.inner.provider, r.inner.timeframe)
+                } else {
+                    encode(resp, r.inner.provider, encoding)
+                },
+            }
+        })
+        .buffered(config.max_concurrent)
+        .collect()
+        .await;
+
+    Ok(WorkerResponse::new(responses))
+}
+
+async fn fetch(client: &reqwest::Client, r: &Request, config: &Config) -> Response {
+    match r.provider.request(client, r) {
+        Some(req) => send(req, config).await,
+        None => Response {
+            err: Some(format!(
+                "provider {:?} doesn't support pool {} (network: {})",
+                r.provider, r.pool_address, r.network
+            )),
+            ..Default::default()
+        },
+    }
+}
+
+/// Sends the request, retrying (with backoff) on connection errors, timeouts and 5xx responses.
+/// Rate limited requests aren't retried, since the scanner backs off for the region instead.
+async fn send(req: reqwest::RequestBuilder, config: &Config) -> Response {
+    let start = Instant::now();
+    let req = req.timeout(config.timeout);
+    let mut retries = 0;
+    loop {
+        let (mut resp, retry) = match req.try_clone() {
+            Some(r) => attempt(r).await,
+            None => (
+                Response {
+                    err: Some("request can't be retried".into()),
+                    ..Default::default()
+                },
+                false,
+            ),
+        };
+        if !retry || retries >= config.max_retries {
+            resp.retries = retries;
+            resp.elapsed_ms = start.elapsed().as_millis() as u64;
+            return resp;
+        }
+        tokio::time::sleep(config.backoff * 2u32.pow(retries as u32)).await;
+        retries += 1;
+    }
+}
+
+/// Returns the response and whether it's worth retrying.
+async fn attempt(req: reqwest::RequestBuilder) -> (Response, bool) {
+    let resp = match req.send().await {
+        Ok(r) => r,
+        Err(e) => {
+            let retry = e.is_connect() || e.is_timeout();
+            return (
+                Response {
+                    err: Some(e.to_string()),
+                    ..Default::default()
                 },
-                Err(e) => Response {
+                retry,
+            );
+        }
+    };
+    let code = resp.status().as_u16();
+    let server_error = resp.status().is_server_error();
+    match resp.text().await {
+        Ok(body) => (
+            Response {
+                status: Some(code),
+                body: Some(body),
+                ..Default::default()
+            },
+            server_error,
+        ),
+        Err(e) => {
+            let retry = server_error || e.is_timeout();
+            (
+                Response {
                     status: Some(code),
-                    body: None,
                     err: Some(e.to_string()),
+                    ..Default::default()
                 },
-            }
+                retry,
+            )
         }
-    }))
-    .await;
+    }
+}
 
-    Ok(bodies)
+/// Replaces successful response bodies with the encoded candles (if requested). Bodies that
+/// can't be parsed are left as-is for the scanner to report.
+fn encode(mut resp: Response, provider: shared::ProviderId, encoding: Encoding) -> Response {
+    if encoding == Encoding::Raw || resp.status != Some(200) {
+        return resp;
+    }
+    let candles = match resp.body.as_deref().map(|b| provider.parse(b)) {
+        Some(Ok(c)) => c.encode(encoding == Encoding::CandlesGzip),
+        _ => return resp,
+    };
+    match candles {
+        Ok(c) => {
+            resp.candles = Some(c);
+            resp.body = None;
+        }
+        Err(e) => resp.err = Some(e.to_string()),
+    }
+    resp
+}
+
+/// Replaces successful response bodies with the signals for their candles.
+fn signals(
+    mut resp: Response,
+    provider: shared::ProviderId,
+    timeframe: shared::Timeframe,
+) -> Response {
+    if resp.status != Some(200) {
+        return resp;
+    }
+    let ohlcv = resp
+        .body
+        .as_deref()
+        .map(|b| provider.parse(b).and_then(OHLCVList::try_from));
+    if let Some(Ok(ohlcv)) = ohlcv {
+        resp.signals = Some(Bars::Candles(ohlcv).signals(provider, timeframe));
+        resp.body = None;
+    }
+    resp
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     tracing::init_default_subscriber();
 
-    run(service_fn(handler)).await
+    let config = Config::from_env();
+    let client = reqwest::Client::new();
+    let (client, config) = (&client, &config);
+    run(service_fn(move |event| async move {
+        handler(event, client, config).await
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{send, Config};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn config() -> Config {
+        Config {
+            timeout: Duration::from_millis(300),
+            max_concurrent: 4,
+            max_retries: 2,
+            backoff: Duration::from_millis(10),
+        }
+    }
+
+    /// Serves the given status codes in order (hanging on `None`) and counts the requests.
+    async fn upstream(statuses: Vec<Option<u16>>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = [0; 4096];
+                    let _ = stream.read(&mut buf).await;
+                    match status {
+                        Some(code) => {
+                            let body = format!("status {}", code);
+                            let resp = format!(
+                                "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                                code,
+                                body.len(),
+                                body
+                            );
+                            let _ = stream.write_all(resp.as_bytes()).await;
+                        }
+                        None => tokio::time::sleep(Duration::from_secs(30)).await,
+                    }
+                });
+            }
+        });
+        (format!("http://{}", addr), hits)
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors() {
+        let client = reqwest::Client::new();
+        let (url, hits) = upstream(vec![Some(503), Some(502), Some(200)]).await;
+        let resp = send(client.get(&url), &config()).await;
+        assert_eq!(resp.status, Some(200));
+        assert_eq!(resp.body.as_deref(), Some("status 200"));
+        assert_eq!(resp.retries, 2);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        let (url, hits) = upstream(vec![Some(500); 5]).await;
+        let resp = send(client.get(&url), &config()).await;
+        assert_eq!(resp.status, Some(500));
+        assert_eq!(resp.retries, 2);
+        assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+        // rate limits are left to the scanner
+        let (url, hits) = upstream(vec![Some(429), Some(200)]).await;
+        let resp = send(client.get(&url), &config()).await;
+        assert_eq!(resp.status, Some(429));
+        assert_eq!(resp.retries, 0);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_timeouts_and_connection_errors() {
+        let client = reqwest::Client::new();
+        let (url, hits) = upstream(vec![None, Some(200)]).await;
+        let resp = send(client.get(&url), &config()).await;
+        assert_eq!(resp.status, Some(200));
+        assert_eq!(resp.retries, 1);
+        assert!(resp.elapsed_ms >= 300);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let (url, _) = upstream(vec![None; 3]).await;
+        let resp = send(client.get(&url), &config()).await;
+        assert!(resp.status.is_none());
+        assert!(resp.err.is_some());
+        assert_eq!(resp.retries, 2);
+
+        // nothing listening
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let resp = send(client.get(format!("http://{}", addr)), &config()).await;
+        assert!(resp.err.is_some());
+        assert_eq!(resp.retries, 2);
+    }
 }
@@ -13,7 +13,7 @@ fn lines_to_vec(file: &str) -> Vec<String> {
         .split("\n")
         .filter_map(|l| {
             let l = l.trim();
-            if l == "" {
+            if l.is_empty() {
                 None
             } else {
                 Some(l.into())
@@ -23,7 +23,7 @@ fn lines_to_vec(file: &str) -> Vec<String> {
 }
 
 fn vec_to_const(fd: &mut File, const_name: &str, vec: Vec<String>) {
-    fd.write(
+    fd.write_all(
         format!(
             "pub const {}: [&str; {}] = {:?};\n",
             const_name,
@@ -0,0 +1,156 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+
+use std::io::{Read, Write};
+
+use super::{Error, Result};
+
+const PLAIN: u8 = 0;
+const GZIP: u8 = 1;
+
+/// How the worker returns the fetched candles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// Provider's response body as-is (parsed by the scanner)
+    #[default]
+    Raw,
+    /// Candles parsed by the worker (see `Candles::encode`)
+    Candles,
+    /// Same as `Candles`, but gzip'd
+    CandlesGzip,
+}
+
+/// OHLCV candles in a columnar layout (in the order served by the provider).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Candles {
+    pub timestamps: Vec<i64>,
+    pub open: Vec<f64>,
+    pub high: Vec<f64>,
+    pub low: Vec<f64>,
+    pub close: Vec<f64>,
+    pub volume: Vec<f64>,
+}
+
+impl Candles {
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+
+    pub fn push(
+        &mut self,
+        timestamp: i64,
+        open: f64,
+        high: f64,
+        low: f64,
+        close: f64,
+        volume: f64,
+    ) {
+        self.timestamps.push(timestamp);
+        self.open.push(open);
+        self.high.push(high);
+        self.low.push(low);
+        self.close.push(close);
+        self.volume.push(volume);
+    }
+
+    /// Encodes the candles for the response payload (base64 of a format byte followed by the
+    /// little-endian count and columns, gzip'd if requested).
+    pub fn encode(&self, gzip: bool) -> Result<String> {
+        let mut buf = Vec::with_capacity(4 + self.len() * 48);
+        buf.extend((self.len() as u32).to_le_bytes());
+        buf.extend(self.timestamps.iter().flat_map(|t| t.to_le_bytes()));
+        for column in [&self.open, &self.high, &self.low, &self.close, &self.volume] {
+            buf.extend(column.iter().flat_map(|v| v.to_le_bytes()));
+        }
+
+        let mut out = vec![if gzip { GZIP } else { PLAIN }];
+        if gzip {
+            let mut encoder = GzEncoder::new(out, Compression::default());
+            encoder.write_all(&buf)?;
+            out = encoder.finish()?;
+        } else {
+            out.extend(buf);
+        }
+        Ok(STANDARD.encode(out))
+    }
+
+    pub fn decode(data: &str) -> Result<Self> {
+        let raw = STANDARD
+            .decode(data)
+            .map_err(|e| Error::UnexpectedResponse(format!("invalid candles encoding: {}", e)))?;
+        let buf = match raw.split_first() {
+            Some((&PLAIN, rest)) => rest.to_vec(),
+            Some((&GZIP, rest)) => {
+                let mut buf = vec![];
+                GzDecoder::new(rest).read_to_end(&mut buf)?;
+                buf
+            }
+            Some((f, _)) => {
+                return Err(Error::UnexpectedResponse(format!(
+                    "unknown candles format {}",
+                    f
+                )))
+            }
+            None => return Err(Error::NoPayload),
+        };
+
+        let len = buf
+            .get(..4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or(Error::NoPayload)?;
+        if buf.len() != 4 + len * 48 {
+            return Err(Error::UnexpectedResponse(format!(
+                "expected {} candles in {} bytes",
+                len,
+                buf.len()
+            )));
+        }
+        let mut columns = buf[4..]
+            .chunks_exact(8)
+            .map(|b| <[u8; 8]>::try_from(b).unwrap());
+        let mut take = |n| columns.by_ref().take(n).collect::<Vec<_>>();
+        let timestamps = take(len).into_iter().map(i64::from_le_bytes).collect();
+        let mut floats = || take(len).into_iter().map(f64::from_le_bytes).collect();
+        Ok(Candles {
+            timestamps,
+            open: floats(),
+            high: floats(),
+            low: floats(),
+            close: floats(),
+            volume: floats(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Candles;
+
+    #[test]
+    fn encode_decode() {
+        let mut candles = Candles::default();
+        for i in 0..500 {
+            let p = 0.001 * i as f64;
+            candles.push(1717977600 + i * 86400, p, p * 1.2, p * 0.9, p * 1.1, 1e6);
+        }
+        for gzip in [false, true] {
+            let encoded = candles.encode(gzip).unwrap();
+            assert_eq!(Candles::decode(&encoded).unwrap(), candles);
+        }
+        assert!(candles.encode(true).unwrap().len() < candles.encode(false).unwrap().len());
+        assert!(Candles::decode(&Candles::default().encode(true).unwrap())
+            .unwrap()
+            .is_empty());
+
+        // truncated payloads are rejected
+        let mut encoded = candles.encode(false).unwrap();
+        encoded.truncate(encoded.len() - 12);
+        assert!(Candles::decode(&encoded).is_err());
+    }
+}
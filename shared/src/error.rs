@@ -5,7 +5,7 @@ pub enum Error {
     #[error("Configuration error: {0}")]
     Config(String),
     #[error("AWS SDK error: {0}")]
-    AwsSdk(#[from] aws_sdk_lambda::Error),
+    AwsSdk(Box<aws_sdk_lambda::Error>),
     #[error("Serde error: {0}")]
     Serde(#[from] serde_json::Error),
     #[error("IO error: {0}")]
@@ -25,7 +25,7 @@ pub enum Error {
     #[error("Invalid timestamp: {0}")]
     InvalidTimestamp(i64),
     #[error("Storage error: {0}")]
-    Storage(#[from] redb::Error),
+    Storage(Box<redb::Error>),
     #[error("Batch error: {0}")]
     Batch(#[from] std::sync::Arc<Error>),
 }
@@ -34,12 +34,14 @@ macro_rules! impl_from(
     ($from:ty, $to:ident) => {
         impl From<$from> for Error {
             fn from(e: $from) -> Self {
-                Error::$to(e.into())
+                Error::$to(Box::new(e.into()))
             }
         }
     };
 );
 
+impl_from!(aws_sdk_lambda::Error, AwsSdk);
+impl_from!(redb::Error, Storage);
 impl_from!(redb::DatabaseError, Storage);
 impl_from!(redb::StorageError, Storage);
 impl_from!(redb::CommitError, Storage);
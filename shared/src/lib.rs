@@ -1,27 +1,79 @@
+#![recursion_limit = "512"]
+
 use serde::{Deserialize, Serialize};
 
+mod candles;
 mod emitted {
     include!(concat!(env!("OUT_DIR"), "/build.rs"));
 }
 mod error;
+pub mod ohlcv;
+mod protocol;
+mod provider;
 
+pub use self::candles::{Candles, Encoding};
 pub use self::emitted::{AWS_REGIONS, IGNORED_POOLS};
 pub use self::error::{Error, Result};
+pub use self::protocol::{Tagged, WorkerRequest, WorkerResponse, PROTOCOL_VERSION};
+pub use self::provider::{Birdeye, CoinGecko, GeckoTerminal, ProviderId, Timeframe};
+
+/// Version of the scanner and the worker (deployed functions need to be compatible with it)
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Request {
     pub network: String,
     pub pool_address: String,
     pub token: Option<(String, String)>,
+    #[serde(default)]
+    pub provider: ProviderId,
+    /// Duration of the candles (daily if unset)
+    #[serde(default)]
+    pub timeframe: Timeframe,
+    /// Number of candles (up to the provider's maximum, 1000, if unset)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u16>,
+    /// Needed by CoinGecko (which looks up the candles by token)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_token_address: Option<String>,
+    // fields below are only used by the scanner (which checkpoints them itself)
     #[serde(skip)]
     pub mc_or_fdv: Option<f64>,
     #[serde(skip)]
     pub maybe_duplicate: bool,
+    /// Token in which the prices are denominated (for building candles from swaps)
+    #[serde(skip)]
+    pub quote_token_address: Option<String>,
+    /// Feeds (and their ranks) which surfaced this pool.
+    #[serde(skip)]
+    pub sources: Vec<(String, usize)>,
+    /// Other pools of the same base token which weren't analyzed.
+    #[serde(skip)]
+    pub siblings: Vec<String>,
+    #[serde(skip)]
+    pub risk_flags: Vec<String>,
+    /// Whether the pool is in the watchlist (analyzed every cycle).
+    #[serde(skip)]
+    pub watched: bool,
+    #[serde(skip)]
+    pub note: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Response {
     pub status: Option<u16>,
     pub body: Option<String>,
+    /// Candles parsed by the worker (see `Encoding`), in which case the body is skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub candles: Option<String>,
+    /// Signals found by the worker (if it was asked to analyze the candles)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signals: Option<ohlcv::Signals>,
     pub err: Option<String>,
+    /// Time taken by the worker for the request (including retries)
+    #[serde(default)]
+    pub elapsed_ms: u64,
+    /// Attempts made by the worker after the first one
+    #[serde(default)]
+    pub retries: u8,
 }
@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+
+use super::{Encoding, Request, Response};
+
+/// Version of the envelope exchanged between the scanner and the workers
+/// (bumped whenever either side can no longer understand the other).
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// Payload of a worker invocation.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerRequest {
+    /// Only asks for the worker's versions (without fetching anything)
+    Handshake { version: u16 },
+    Fetch {
+        version: u16,
+        requests: Vec<Tagged<Request>>,
+        /// Older workers ignore this and always return the raw bodies
+        #[serde(default)]
+        encoding: Encoding,
+        /// Return only the signals (and the last few candles) instead of the candles
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        analyze: bool,
+    },
+}
+
+impl WorkerRequest {
+    pub fn handshake() -> Self {
+        WorkerRequest::Handshake {
+            version: PROTOCOL_VERSION,
+        }
+    }
+
+    pub fn fetch(requests: Vec<Tagged<Request>>, encoding: Encoding, analyze: bool) -> Self {
+        WorkerRequest::Fetch {
+            version: PROTOCOL_VERSION,
+            requests,
+            encoding,
+            analyze,
+        }
+    }
+
+    pub fn version(&self) -> u16 {
+        match self {
+            WorkerRequest::Handshake { version } | WorkerRequest::Fetch { version, .. } => *version,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WorkerResponse {
+    pub version: u16,
+    /// Version of the worker (see `shared::VERSION`)
+    pub worker_version: String,
+    #[serde(default)]
+    pub responses: Vec<Tagged<Response>>,
+    /// Set if the worker couldn't handle the request (e.g., unsupported protocol version)
+    #[serde(default)]
+    pub err: Option<String>,
+}
+
+impl WorkerResponse {
+    pub fn new(responses: Vec<Tagged<Response>>) -> Self {
+        WorkerResponse {
+            version: PROTOCOL_VERSION,
+            worker_version: super::VERSION.into(),
+            responses,
+            err: None,
+        }
+    }
+}
+
+/// Request or response along with the id for correlating them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Tagged<T> {
+    pub id: u64,
+    #[serde(flatten)]
+    pub inner: T,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Tagged, WorkerRequest, PROTOCOL_VERSION};
+    use crate::{Encoding, ProviderId, Request};
+
+    #[test]
+    fn envelope_format() {
+        let req = WorkerRequest::fetch(
+            vec![Tagged {
+                id: 7,
+                inner: Request {
+                    network: "solana".into(),
+                    pool_address: "abc".into(),
+                    provider: ProviderId::Birdeye,
+                    limit: Some(30),
+                    sources: vec![("gt-top".into(), 3)],
+                    ..Default::default()
+                },
+            }],
+            Encoding::CandlesGzip,
+            true,
+        );
+        let json = serde_json::to_value(&req).unwrap();
+        assert_eq!(json["type"], "fetch");
+        assert_eq!(json["version"], PROTOCOL_VERSION);
+        assert_eq!(json["requests"][0]["id"], 7);
+        assert_eq!(json["requests"][0]["pool_address"], "abc");
+        assert_eq!(json["requests"][0]["timeframe"], "day");
+        assert_eq!(json["requests"][0]["limit"], 30);
+        // (only the scanner needs the sources)
+        assert!(json["requests"][0].get("sources").is_none());
+        assert_eq!(json["encoding"], "candles_gzip");
+        assert_eq!(json["analyze"], true);
+
+        let parsed: WorkerRequest = serde_json::from_value(json).unwrap();
+        match parsed {
+            WorkerRequest::Fetch { requests, .. } => {
+                assert_eq!(requests[0].id, 7);
+                assert_eq!(requests[0].inner.provider, ProviderId::Birdeye);
+            }
+            _ => panic!("expected fetch"),
+        }
+
+        // requests from older scanners get the raw bodies
+        let parsed: WorkerRequest =
+            serde_json::from_str(r#"{"type":"fetch","version":1,"requests":[]}"#).unwrap();
+        assert!(matches!(
+            parsed,
+            WorkerRequest::Fetch {
+                encoding: Encoding::Raw,
+                analyze: false,
+                ..
+            }
+        ));
+
+        let handshake: WorkerRequest =
+            serde_json::from_str(r#"{"type":"handshake","version":1}"#).unwrap();
+        assert_eq!(handshake.version(), 1);
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+
+use crate::Candles;
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Item {
+    o: f64,
+    h: f64,
+    l: f64,
+    c: f64,
+    #[serde(default)]
+    v: f64,
+    unix_time: i64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Items {
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Birdeye {
+    #[serde(default)]
+    success: bool,
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    data: Option<Items>,
+}
+
+impl Birdeye {
+    pub fn candles(&self) -> crate::Result<Candles> {
+        match (&self.data, self.success) {
+            (Some(data), true) => {
+                let mut candles = Candles::default();
+                for i in &data.items {
+                    candles.push(i.unix_time, i.o, i.h, i.l, i.c, i.v);
+                }
+                Ok(candles)
+            }
+            _ => Err(crate::Error::UnexpectedResponse(
+                self.message.clone().unwrap_or_default(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Birdeye;
+    use serde_json::json;
+
+    #[test]
+    fn extract_ohlcv() {
+        let data: Birdeye = serde_json::from_value(json!({"data":{"items":[{"o":0.00195141461717062,"h":0.00231,"l":0.00183,"c":0.00221,"v":6375746.97775684,"unixTime":1717977600,"address":"57Tu1cFCTwCQnYu4qeatEeLKkcHxqBXzgZ2dKaEyNS8F","type":"1D"},{"o":0.00221,"h":0.00245,"l":0.0019,"c":0.002,"v":5120331.1,"unixTime":1718064000,"address":"57Tu1cFCTwCQnYu4qeatEeLKkcHxqBXzgZ2dKaEyNS8F","type":"1D"}]},"success":true})).unwrap();
+        let candles = data.candles().expect("unwrapping candles");
+        assert_eq!(candles.len(), 2);
+        assert_eq!(candles.timestamps[1], 1718064000);
+        assert_eq!(candles.open[1], 0.00221);
+        assert_eq!(candles.close[1], 0.002);
+
+        let data: Birdeye =
+            serde_json::from_value(json!({"success":false,"message":"Unauthorized"})).unwrap();
+        assert!(data.candles().is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use serde::Deserialize;
+
+use crate::Candles;
+
+const DAY_MILLIS: i64 = 86_400_000;
+
+/// CoinGecko only serves price points (and rolling volumes) for listed tokens, so
+/// candles are approximated from them, with each day opening at the previous close.
+#[derive(Deserialize, Debug)]
+pub struct CoinGecko {
+    #[serde(default)]
+    prices: Vec<(f64, f64)>,
+    #[serde(default)]
+    total_volumes: Vec<(f64, f64)>,
+}
+
+impl CoinGecko {
+    pub fn candles(&self) -> crate::Result<Candles> {
+        let mut candles = Candles::default();
+        let mut volumes = self.total_volumes.iter().peekable();
+        for &(t, price) in &self.prices {
+            // daily points are at midnight and denote the close of the previous day
+            let day = (t as i64 - 1).div_euclid(DAY_MILLIS) * DAY_MILLIS / 1000;
+            let mut volume = None;
+            while let Some(&&(vt, v)) = volumes.peek() {
+                if vt > t {
+                    break;
+                }
+                volume = Some(v);
+                volumes.next();
+            }
+            if candles.timestamps.last() == Some(&day) {
+                let i = candles.len() - 1;
+                candles.high[i] = candles.high[i].max(price);
+                candles.low[i] = candles.low[i].min(price);
+                candles.close[i] = price;
+                candles.volume[i] = volume.unwrap_or(candles.volume[i]);
+                continue;
+            }
+            let open = candles.close.last().copied().unwrap_or(price);
+            candles.push(
+                day,
+                open,
+                open.max(price),
+                open.min(price),
+                price,
+                volume.unwrap_or_default(),
+            );
+        }
+        Ok(candles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoinGecko;
+    use serde_json::json;
+
+    #[test]
+    fn extract_ohlcv() {
+        let data: CoinGecko = serde_json::from_value(json!({"prices":[[1717977600000i64,2.12],[1718064000000i64,2.31],[1718150400000i64,2.07],[1718193487000i64,2.1]],"market_caps":[[1717977600000i64,53100000.0],[1718064000000i64,57800000.0],[1718150400000i64,51800000.0],[1718193487000i64,52500000.0]],"total_volumes":[[1717977600000i64,48000000.0],[1718064000000i64,51000000.0],[1718150400000i64,51750687.0],[1718193487000i64,50000000.0]]})).unwrap();
+        let candles = data.candles().expect("unwrapping candles");
+        assert_eq!(candles.len(), 4);
+        assert_eq!(candles.timestamps[2], 1718064000);
+        assert_eq!(candles.open[2], 2.31);
+        assert_eq!(candles.close[2], 2.07);
+        assert_eq!(candles.high[2], 2.31);
+        assert_eq!(candles.low[2], 2.07);
+        assert_eq!(candles.volume[2], 51750687.0);
+        // ongoing day
+        assert_eq!(candles.timestamps[3], 1718150400);
+        assert_eq!(candles.open[3], 2.07);
+    }
+}
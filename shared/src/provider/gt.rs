@@ -0,0 +1,44 @@
+use serde::Deserialize;
+
+use crate::Candles;
+
+#[derive(Deserialize, Debug)]
+struct OHLCVList {
+    ohlcv_list: Vec<(i64, f64, f64, f64, f64, f64)>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RespData {
+    attributes: OHLCVList,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RespError {
+    error_code: u16,
+    error_message: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum GeckoTerminal {
+    Success { data: RespData },
+    Failure { status: RespError },
+}
+
+impl GeckoTerminal {
+    pub fn candles(&self) -> crate::Result<Candles> {
+        match self {
+            GeckoTerminal::Success { data } => {
+                let mut candles = Candles::default();
+                for &(t, o, h, l, c, v) in &data.attributes.ohlcv_list {
+                    candles.push(t, o, h, l, c, v);
+                }
+                Ok(candles)
+            }
+            GeckoTerminal::Failure { status } => Err(crate::Error::UnexpectedStatusCode(
+                status.error_code,
+                Some(status.error_message.clone()),
+            )),
+        }
+    }
+}
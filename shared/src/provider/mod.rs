@@ -0,0 +1,249 @@
+use chrono::{offset::Utc, DateTime, Datelike};
+use serde::{Deserialize, Serialize};
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{Candles, Request};
+
+mod birdeye;
+mod coingecko;
+mod gt;
+
+pub use self::birdeye::Birdeye;
+pub use self::coingecko::CoinGecko;
+pub use self::gt::GeckoTerminal;
+
+const MAX_CANDLES: u64 = 1000;
+
+/// Duration of the candles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timeframe {
+    #[default]
+    Day,
+    FourHours,
+    Hour,
+}
+
+impl Timeframe {
+    pub const VARIANTS: [Timeframe; 3] = [Timeframe::Day, Timeframe::FourHours, Timeframe::Hour];
+
+    pub fn secs(&self) -> u64 {
+        match self {
+            Timeframe::Day => 86400,
+            Timeframe::FourHours => 4 * 3600,
+            Timeframe::Hour => 3600,
+        }
+    }
+
+    /// Label of the candles grouped by three (e.g., `12H` for `4H`).
+    pub fn grouped_name(&self) -> &'static str {
+        match self {
+            Timeframe::Day => "3D",
+            Timeframe::FourHours => "12H",
+            Timeframe::Hour => "3H",
+        }
+    }
+
+    /// Whether a group of three candles starts with the candle at the given time (the
+    /// three-day candles start on the days of the year 1, 4, 7 and so on).
+    pub fn starts_group(&self, time: DateTime<Utc>) -> bool {
+        match self {
+            Timeframe::Day => time.ordinal() % 3 == 1,
+            tf => (time.timestamp() as u64 / tf.secs()).is_multiple_of(3),
+        }
+    }
+}
+
+/// Source of the OHLCV data for a pool.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderId {
+    #[default]
+    GeckoTerminal,
+    Birdeye,
+    CoinGecko,
+    /// Candles built from on-chain swaps by the scanner itself
+    Rpc,
+}
+
+impl ProviderId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProviderId::GeckoTerminal => "gecko_terminal",
+            ProviderId::Birdeye => "birdeye",
+            ProviderId::CoinGecko => "coin_gecko",
+            ProviderId::Rpc => "rpc",
+        }
+    }
+
+    /// Whether the candles have actual highs and lows (CoinGecko's candles are
+    /// approximated from daily price points, so their wicks only span the bodies).
+    pub fn has_wicks(&self) -> bool {
+        !matches!(self, ProviderId::CoinGecko)
+    }
+
+    /// Whether the provider can serve OHLCV data for the request.
+    pub fn supports(&self, req: &Request) -> bool {
+        match self {
+            ProviderId::GeckoTerminal => true,
+            ProviderId::Birdeye => birdeye_chain(&req.network).is_some(),
+            // market charts only have daily granularity for longer ranges
+            ProviderId::CoinGecko => {
+                coingecko_platform(&req.network).is_some()
+                    && req.base_token_address.is_some()
+                    && req.timeframe == Timeframe::Day
+            }
+            // the swaps are bucketed by day
+            ProviderId::Rpc => req.network != "ton" && req.timeframe == Timeframe::Day,
+        }
+    }
+
+    /// Parses the candles from the response body served by the provider.
+    pub fn parse(&self, body: &str) -> crate::Result<Candles> {
+        match self {
+            ProviderId::GeckoTerminal => serde_json::from_str::<GeckoTerminal>(body)?.candles(),
+            ProviderId::Birdeye => serde_json::from_str::<Birdeye>(body)?.candles(),
+            ProviderId::CoinGecko => serde_json::from_str::<CoinGecko>(body)?.candles(),
+            ProviderId::Rpc => Err(crate::Error::Config(
+                "RPC candles aren't fetched through hosts".into(),
+            )),
+        }
+    }
+
+    /// Builds the HTTP request for fetching the candles (`None` if unsupported).
+    pub fn request(
+        &self,
+        client: &reqwest::Client,
+        req: &Request,
+    ) -> Option<reqwest::RequestBuilder> {
+        let limit = req
+            .limit
+            .map(|l| (l as u64).min(MAX_CANDLES))
+            .unwrap_or(MAX_CANDLES);
+        match self {
+            ProviderId::GeckoTerminal => {
+                let (timeframe, aggregate) = match req.timeframe {
+                    Timeframe::Day => ("day", 1),
+                    Timeframe::FourHours => ("hour", 4),
+                    Timeframe::Hour => ("hour", 1),
+                };
+                Some(client.get(format!(
+                    "https://api.geckoterminal.com/api/v2/networks/{network}/pools/{pool}/ohlcv/{timeframe}?aggregate={aggregate}&limit={limit}",
+                    network = req.network,
+                    pool = req.pool_address,
+                )))
+            }
+            ProviderId::Birdeye => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_secs())
+                    .unwrap_or_default();
+                let builder = client
+                    .get(format!(
+                        "https://public-api.birdeye.so/defi/ohlcv/pair?address={pool}&type={timeframe}&time_from={from}&time_to={to}",
+                        pool = req.pool_address,
+                        timeframe = match req.timeframe {
+                            Timeframe::Day => "1D",
+                            Timeframe::FourHours => "4H",
+                            Timeframe::Hour => "1H",
+                        },
+                        from = now.saturating_sub(limit * req.timeframe.secs()),
+                        to = now,
+                    ))
+                    .header("x-chain", birdeye_chain(&req.network)?)
+                    .header("Accept", "application/json");
+                Some(match std::env::var("BIRDEYE_API_KEY") {
+                    Ok(key) => builder.header("X-API-KEY", key),
+                    Err(_) => builder,
+                })
+            }
+            ProviderId::CoinGecko => {
+                let url = format!(
+                    "https://api.coingecko.com/api/v3/coins/{platform}/contract/{token}/market_chart?vs_currency=usd&days={days}&interval=daily",
+                    platform = coingecko_platform(&req.network)?,
+                    token = req.base_token_address.as_ref()?,
+                    days = limit,
+                );
+                let builder = client.get(url).header("Accept", "application/json");
+                Some(match std::env::var("COINGECKO_API_KEY") {
+                    Ok(key) => builder.header("x-cg-demo-api-key", key),
+                    Err(_) => builder,
+                })
+            }
+            ProviderId::Rpc => None,
+        }
+    }
+}
+
+// network names are geckoterminal's
+fn birdeye_chain(network: &str) -> Option<&'static str> {
+    match network {
+        "solana" => Some("solana"),
+        "eth" => Some("ethereum"),
+        "base" => Some("base"),
+        "bsc" => Some("bsc"),
+        "arbitrum" => Some("arbitrum"),
+        "avax" => Some("avalanche"),
+        "optimism" => Some("optimism"),
+        _ => None,
+    }
+}
+
+fn coingecko_platform(network: &str) -> Option<&'static str> {
+    match network {
+        "solana" => Some("solana"),
+        "eth" => Some("ethereum"),
+        "base" => Some("base"),
+        "ton" => Some("the-open-network"),
+        "blast" => Some("blast"),
+        "bsc" => Some("binance-smart-chain"),
+        "arbitrum" => Some("arbitrum-one"),
+        "avax" => Some("avalanche"),
+        "optimism" => Some("optimistic-ethereum"),
+        "ftm" => Some("fantom"),
+        "metis" => Some("metis-andromeda"),
+        "ronin" => Some("ronin"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProviderId, Timeframe};
+    use crate::Request;
+
+    fn url(provider: ProviderId, req: &Request) -> String {
+        let client = reqwest::Client::new();
+        let builder = provider.request(&client, req).unwrap();
+        builder.build().unwrap().url().to_string()
+    }
+
+    #[test]
+    fn request_timeframe_and_limit() {
+        let mut req = Request {
+            network: "solana".into(),
+            pool_address: "pool1".into(),
+            base_token_address: Some("token1".into()),
+            ..Default::default()
+        };
+        assert!(url(ProviderId::GeckoTerminal, &req).ends_with("/ohlcv/day?aggregate=1&limit=1000"));
+        assert!(url(ProviderId::CoinGecko, &req).contains("&days=1000&"));
+
+        req.timeframe = Timeframe::FourHours;
+        req.limit = Some(60);
+        assert!(url(ProviderId::GeckoTerminal, &req).ends_with("/ohlcv/hour?aggregate=4&limit=60"));
+        let birdeye = url(ProviderId::Birdeye, &req);
+        assert!(birdeye.contains("&type=4H&"));
+        // 60 4-hour candles
+        let range = birdeye
+            .split('&')
+            .filter_map(|p| p.split_once('='))
+            .filter(|(k, _)| k.starts_with("time_"))
+            .map(|(_, v)| v.parse::<u64>().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(range[1] - range[0], 60 * 4 * 3600);
+        // market charts only have daily granularity
+        assert!(!ProviderId::CoinGecko.supports(&req));
+    }
+}
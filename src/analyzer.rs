@@ -0,0 +1,143 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use shared::ohlcv::{Analysis, Bars, OHLCVList};
+
+use super::{
+    host::Host,
+    notifier::Notifier,
+    provider::{Provider, RpcSwaps},
+};
+
+/// Analysis of a single pool (as returned by the HTTP API).
+#[derive(Default, Serialize)]
+pub struct Report {
+    pub provider: shared::ProviderId,
+    pub daily: Option<Analysis>,
+    pub three_day: Option<Analysis>,
+}
+
+/// Checks whether the provider can fetch candles for the request.
+pub fn supports(
+    rpc: Option<&RpcSwaps>,
+    provider: shared::ProviderId,
+    req: &shared::Request,
+) -> bool {
+    provider.supports(req)
+        && (provider != shared::ProviderId::Rpc
+            || rpc.map(|r| r.supports(&req.network)).unwrap_or(false))
+}
+
+/// Analyzes individual pools on demand, sharing the hosts and notifiers with the runner.
+pub struct Analyzer<P> {
+    pub(crate) hosts: Vec<Arc<dyn Host<P> + Send + Sync + 'static>>,
+    pub(crate) rpc: Option<Arc<RpcSwaps>>,
+    pub(crate) notifier: HashMap<String, Arc<dyn Notifier + Send + Sync + 'static>>,
+    pub(crate) providers: Vec<shared::ProviderId>,
+}
+
+impl<P> Clone for Analyzer<P> {
+    fn clone(&self) -> Self {
+        Analyzer {
+            hosts: self.hosts.clone(),
+            rpc: self.rpc.clone(),
+            notifier: self.notifier.clone(),
+            providers: self.providers.clone(),
+        }
+    }
+}
+
+impl<P> Analyzer<P>
+where
+    P: Provider + Send + Sync + 'static,
+{
+    /// Fetches the candles for the pool (trying the configured providers in order)
+    /// and analyzes them, optionally posting the analysis to the network's notifier.
+    pub async fn analyze(
+        &self,
+        network: &str,
+        pool_address: &str,
+        notify: bool,
+    ) -> shared::Result<Report> {
+        let mut req = shared::Request {
+            network: network.into(),
+            pool_address: pool_address.into(),
+            ..Default::default()
+        };
+        let mut last_err = None;
+        for &provider in &self.providers {
+            if !supports(self.rpc.as_deref(), provider, &req) {
+                continue;
+            }
+            req.provider = provider;
+            log::info!(
+                "analyzing pool {} (network: {}) with provider {:?}",
+                pool_address,
+                network,
+                provider
+            );
+            let ohlcv = match self.fetch(&req).await {
+                Ok(o) => o,
+                // try another provider if this one doesn't have the pool or is throttling us
+                Err(e @ shared::Error::UnexpectedStatusCode(400..=499, _)) => {
+                    log::warn!("provider {:?} failed: {}", provider, e);
+                    last_err = Some(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if notify {
+                let notifier = self.notifier.get(network).ok_or_else(|| {
+                    shared::Error::Config(format!("no notifier for network {}", network))
+                })?;
+                notifier
+                    .post_analysis(&req, Bars::Candles(ohlcv.clone()))
+                    .await?;
+            }
+            return Ok(Report {
+                provider,
+                three_day: ohlcv.clone().grouped(req.timeframe).analyze(req.timeframe),
+                daily: ohlcv.analyze(req.timeframe),
+            });
+        }
+
+        Err(last_err.unwrap_or_else(|| {
+            shared::Error::Config(format!("no provider supports network {}", network))
+        }))
+    }
+
+    async fn fetch(&self, req: &shared::Request) -> shared::Result<OHLCVList> {
+        if req.provider == shared::ProviderId::Rpc {
+            return match &self.rpc {
+                Some(rpc) => rpc.ohlcv_data(req).await,
+                None => Err(shared::Error::Config("RPC provider not configured".into())),
+            };
+        }
+        let host = self
+            .hosts
+            .first()
+            .ok_or_else(|| shared::Error::Config("no hosts configured".into()))?;
+        let region = host
+            .regions()
+            .into_iter()
+            .next()
+            .ok_or_else(|| shared::Error::Config("no healthy host regions".into()))?;
+        // the full history is needed here (not just the signals)
+        match host
+            .trigger(vec![(region, vec![req.clone()])], false)
+            .await
+            .into_iter()
+            .flatten()
+            .next()
+            .ok_or(shared::Error::NoPayload)??
+        {
+            Bars::Candles(ohlcv) => Ok(ohlcv),
+            Bars::Signals(_) => Err(shared::Error::UnexpectedResponse(
+                "expected candles, got signals".into(),
+            )),
+        }
+    }
+}
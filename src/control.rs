@@ -0,0 +1,177 @@
+use chrono::{offset::Utc, DateTime};
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::feed::Network;
+
+/// Snapshot of what the runner is currently doing.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct State {
+    /// Networks being scanned
+    pub scans: Vec<ScanState>,
+    pub buffer_len: usize,
+    pub paused: bool,
+    /// Next scheduled scan for each network
+    pub next_runs: HashMap<String, DateTime<Utc>>,
+    /// Set when the runner is waiting for the next scheduled scan
+    pub waiting_until: Option<DateTime<Utc>>,
+    pub last_cycle_finished: Option<DateTime<Utc>>,
+    pub running: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanState {
+    pub network: String,
+    pub page: u16,
+    pub ended_feeds: Vec<bool>,
+}
+
+#[derive(Default)]
+struct Inner {
+    running: AtomicBool,
+    shutdown: AtomicBool,
+    shutdown_requested: Notify,
+    paused: AtomicBool,
+    resumed: Notify,
+    reload: AtomicBool,
+    /// Networks requested to be scanned (in order, without duplicates)
+    scans: Mutex<VecDeque<Network>>,
+    scan_requested: Notify,
+    state: Mutex<State>,
+}
+
+/// Handle for controlling the runner at runtime (shared with the HTTP API).
+#[derive(Clone, Default)]
+pub struct Control {
+    inner: Arc<Inner>,
+}
+
+/// Marks the runner as stopped when dropped (including panics).
+pub struct RunningGuard(Control);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.inner.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Control {
+    pub fn mark_running(&self) -> RunningGuard {
+        self.inner.running.store(true, Ordering::SeqCst);
+        RunningGuard(self.clone())
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.inner.running.load(Ordering::SeqCst)
+    }
+
+    /// Asks the runner (and the server) to stop after the in-flight work.
+    pub fn request_shutdown(&self) {
+        self.inner.shutdown.store(true, Ordering::SeqCst);
+        self.inner.shutdown_requested.notify_waiters();
+        // also wake up the runner if it's paused
+        self.inner.resumed.notify_waiters();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.inner.shutdown.load(Ordering::SeqCst)
+    }
+
+    pub async fn shutdown_requested(&self) {
+        loop {
+            let requested = self.inner.shutdown_requested.notified();
+            if self.is_shutting_down() {
+                return;
+            }
+            requested.await;
+        }
+    }
+
+    pub fn pause(&self) {
+        self.inner.paused.store(true, Ordering::SeqCst);
+    }
+
+    pub fn resume(&self) {
+        self.inner.paused.store(false, Ordering::SeqCst);
+        self.inner.resumed.notify_waiters();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.inner.paused.load(Ordering::SeqCst)
+    }
+
+    pub async fn wait_if_paused(&self) {
+        loop {
+            // register before checking so that a resume in between isn't missed
+            let resumed = self.inner.resumed.notified();
+            if !self.is_paused() || self.is_shutting_down() {
+                return;
+            }
+            log::info!("runner paused, waiting for resume");
+            resumed.await;
+        }
+    }
+
+    /// Requests a scan of the network without waiting for the next cycle.
+    pub fn request_scan(&self, network: Network) {
+        let mut scans = self.inner.scans.lock().expect("poisoned lock");
+        if !scans.contains(&network) {
+            scans.push_back(network);
+        }
+        self.inner.scan_requested.notify_one();
+    }
+
+    pub async fn scan_requested(&self) -> Network {
+        loop {
+            if let Some(n) = self.take_scan_request() {
+                return n;
+            }
+            self.inner.scan_requested.notified().await;
+        }
+    }
+
+    /// Takes the earliest pending scan request.
+    pub fn take_scan_request(&self) -> Option<Network> {
+        self.inner.scans.lock().expect("poisoned lock").pop_front()
+    }
+
+    pub fn request_reload(&self) {
+        self.inner.reload.store(true, Ordering::SeqCst);
+    }
+
+    pub fn take_reload(&self) -> bool {
+        self.inner.reload.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn state(&self) -> State {
+        let mut state = self.inner.state.lock().expect("poisoned lock").clone();
+        state.paused = self.is_paused();
+        state.running = self.is_running();
+        state
+    }
+
+    pub fn update_state(&self, f: impl FnOnce(&mut State)) {
+        f(&mut self.inner.state.lock().expect("poisoned lock"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Control;
+    use crate::feed::Network;
+
+    #[test]
+    fn queued_scan_requests() {
+        let control = Control::default();
+        control.request_scan(Network::Solana);
+        control.request_scan(Network::Base);
+        control.request_scan(Network::Solana);
+        assert_eq!(control.take_scan_request(), Some(Network::Solana));
+        assert_eq!(control.take_scan_request(), Some(Network::Base));
+        assert_eq!(control.take_scan_request(), None);
+    }
+}
@@ -19,25 +19,32 @@ pub struct PaginatedData<T> {
 #[serde(rename_all = "camelCase")]
 pub struct TokenInfo {
     pub base_token_symbol: String,
+    #[serde(default)]
+    pub base_token_address: String,
     pub pair_contract_address: String,
     pub quoto_token_symbol: String,
     #[serde(default)]
+    pub quoto_token_address: String,
+    #[serde(default)]
     pub fdv: String,
     #[serde(default)]
     pub liquidity: String,
 }
 
-impl Into<Vec<super::Pair>> for Paginated<TokenInfo> {
-    fn into(self) -> Vec<super::Pair> {
-        self.data
+impl From<Paginated<TokenInfo>> for Vec<super::Pair> {
+    fn from(val: Paginated<TokenInfo>) -> Self {
+        val.data
             .page_list
             .into_iter()
             .map(|x| super::Pair {
                 contract_address: x.pair_contract_address,
                 base_token: x.base_token_symbol,
                 quote_token: x.quoto_token_symbol,
+                base_token_address: x.base_token_address,
+                quote_token_address: x.quoto_token_address,
                 mc_or_fdv: x.fdv.parse().ok(),
                 liquidity: x.liquidity.parse().ok(),
+                sources: vec![],
             })
             .collect()
     }
@@ -65,8 +72,12 @@ impl From<super::Network> for Network {
 }
 
 impl super::Feed for CoinMarketCap {
+    const NAME: &'static str = "cmc";
+
     const DELAY: Duration = Duration::from_millis(1100);
 
+    const PAGE_SIZE: usize = 100;
+
     type Response = Paginated<TokenInfo>;
 
     fn modify(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
@@ -77,9 +88,10 @@ impl super::Feed for CoinMarketCap {
 
     fn url(network: super::Network, page: u16) -> String {
         format!(
-            "https://api.coinmarketcap.com/dexer/v3/platformpage/pair-pages?platform-id={}&sort-field=txs24h&desc=true&page={}&pageSize=100",
+            "https://api.coinmarketcap.com/dexer/v3/platformpage/pair-pages?platform-id={}&sort-field=txs24h&desc=true&page={}&pageSize={}",
             Network::from(network).0,
-            page
+            page,
+            Self::PAGE_SIZE
         )
     }
 }
@@ -95,6 +107,14 @@ mod tests {
         let pairs: Vec<Pair> = data.into();
         assert_eq!(pairs[0].base_token, "WETH");
         assert_eq!(pairs[0].quote_token, "USDC");
+        assert_eq!(
+            pairs[0].base_token_address,
+            "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"
+        );
+        assert_eq!(
+            pairs[0].quote_token_address,
+            "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+        );
         assert_eq!(
             pairs[0].contract_address,
             "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"
@@ -12,20 +12,27 @@ pub struct PaginatedData {
     pub data: Vec<TokenInfo>,
 }
 
-impl Into<Vec<super::Pair>> for PaginatedData {
-    fn into(self) -> Vec<super::Pair> {
-        self.data
+impl From<PaginatedData> for Vec<super::Pair> {
+    fn from(val: PaginatedData) -> Self {
+        val.data
             .into_iter()
             .map(|x| {
                 let name = x.attributes.name;
                 let mut name = name.split("/");
                 let fdv = x.attributes.fdv_usd.parse().ok();
+                let (base_token_address, quote_token_address) = x
+                    .relationships
+                    .map(|r| (r.base_token.address(), r.quote_token.address()))
+                    .unwrap_or_default();
                 super::Pair {
                     contract_address: x.attributes.address,
                     base_token: name.next().map(|s| s.trim().into()).unwrap_or_default(),
                     quote_token: name.next().map(|s| s.trim().into()).unwrap_or_default(),
+                    base_token_address,
+                    quote_token_address,
                     mc_or_fdv: fdv,
-                    liquidity: fdv,
+                    liquidity: x.attributes.reserve_in_usd.parse().ok(),
+                    sources: vec![],
                 }
             })
             .collect()
@@ -35,6 +42,35 @@ impl Into<Vec<super::Pair>> for PaginatedData {
 #[derive(Deserialize)]
 pub struct TokenInfo {
     pub attributes: Attributes,
+    #[serde(default)]
+    pub relationships: Option<Relationships>,
+}
+
+#[derive(Deserialize)]
+pub struct Relationships {
+    base_token: Relationship,
+    quote_token: Relationship,
+}
+
+#[derive(Deserialize)]
+pub struct Relationship {
+    data: RelationshipData,
+}
+
+#[derive(Deserialize)]
+pub struct RelationshipData {
+    id: String,
+}
+
+impl Relationship {
+    /// Token IDs are of the form `{network}_{address}`
+    fn address(&self) -> String {
+        self.data
+            .id
+            .rsplit_once('_')
+            .map(|(_, a)| a.into())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize)]
@@ -43,13 +79,19 @@ pub struct Attributes {
     address: String,
     #[serde(default)]
     fdv_usd: String,
+    #[serde(default)]
+    reserve_in_usd: String,
 }
 
 impl super::Feed for GeckoTerminalTop {
+    const NAME: &'static str = "gt-top";
+
     const MAX_PAGES: Option<u16> = Some(10);
 
     const DELAY: Duration = DELAY;
 
+    const PAGE_SIZE: usize = 20;
+
     type Response = PaginatedData;
 
     fn url(network: super::Network, page: u16) -> String {
@@ -61,10 +103,14 @@ impl super::Feed for GeckoTerminalTop {
 }
 
 impl super::Feed for GeckoTerminalTrending {
+    const NAME: &'static str = "gt-trending";
+
     const MAX_PAGES: Option<u16> = Some(10);
 
     const DELAY: Duration = DELAY;
 
+    const PAGE_SIZE: usize = 20;
+
     type Response = PaginatedData;
 
     fn url(network: super::Network, page: u16) -> String {
@@ -86,9 +132,47 @@ mod tests {
         let pairs: Vec<Pair> = data.into();
         assert_eq!(pairs[0].base_token, "Trump");
         assert_eq!(pairs[0].quote_token, "SOL");
+        assert_eq!(
+            pairs[0].base_token_address,
+            "C1XRDFrD9EYNUvi69SD9tZs4nvq3oVWNACUfFHpwWu3y"
+        );
+        assert_eq!(
+            pairs[0].quote_token_address,
+            "So11111111111111111111111111111111111111112"
+        );
         assert_eq!(
             pairs[0].contract_address,
             "7sXvhsvzmuxomqdFAU6fzxY3bukX9KkVcWWCLSw51osX"
         );
+        assert_eq!(pairs[0].liquidity, Some(220.6525));
+    }
+
+    #[test]
+    fn liquidity_from_reserves() {
+        let pool = |address: &str, reserve: &str| {
+            json!({
+                "id": format!("base_{}", address),
+                "type": "pool",
+                "attributes": {
+                    "name": "FOO / WETH",
+                    "address": address,
+                    "fdv_usd": "1500000",
+                    "reserve_in_usd": reserve,
+                },
+                "relationships": {
+                    "base_token": { "data": { "id": "base_0xf00", "type": "token" } },
+                    "quote_token": { "data": { "id": "base_0x4200", "type": "token" } },
+                },
+            })
+        };
+        let data: super::PaginatedData = serde_json::from_value(json!({
+            "data": [pool("0xa", "1200.5"), pool("0xb", "98000")],
+        }))
+        .unwrap();
+        let pairs: Vec<Pair> = data.into();
+        assert_eq!(pairs[0].base_token_address, pairs[1].base_token_address);
+        assert_eq!(pairs[0].mc_or_fdv, pairs[1].mc_or_fdv);
+        assert_eq!(pairs[0].liquidity, Some(1200.5));
+        assert_eq!(pairs[1].liquidity, Some(98000.));
     }
 }
@@ -4,11 +4,15 @@ use serde::de::DeserializeOwned;
 use std::marker::PhantomData;
 use std::time::{Duration, Instant};
 
+use super::metrics;
+
 mod cm;
 mod gt;
 
 #[async_trait::async_trait]
 pub trait FeedClient {
+    fn name(&self) -> &'static str;
+
     async fn fetch_addresses(
         &self,
         network: Network,
@@ -20,7 +24,9 @@ pub type CoinMarketCap = DefaultClient<self::cm::CoinMarketCap>;
 pub type GeckoTerminalTop = DefaultClient<self::gt::GeckoTerminalTop>;
 pub type GeckoTerminalTrending = DefaultClient<self::gt::GeckoTerminalTrending>;
 
-#[derive(Debug, Clone, Copy, strum_macros::Display, strum_macros::VariantArray)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, strum_macros::Display, strum_macros::VariantArray,
+)]
 pub enum Network {
     // serialized as geckoterminal's network names
     #[strum(serialize = "solana")]
@@ -49,19 +55,112 @@ pub enum Network {
     Ronin,
 }
 
+impl Network {
+    /// Whether addresses on this network are hex-encoded (and hence case-insensitive).
+    pub fn is_evm(&self) -> bool {
+        !matches!(self, Network::Solana | Network::TON)
+    }
+}
+
+/// Canonical identity of a pool across feeds.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PoolId {
+    pub network: Network,
+    pub address: String,
+}
+
+impl PoolId {
+    pub fn new(network: Network, address: &str) -> Self {
+        PoolId {
+            network,
+            address: normalize_address(network, address),
+        }
+    }
+}
+
+/// Normalizes a pool or token address so that it can be compared across feeds.
+pub fn normalize_address(network: Network, address: &str) -> String {
+    let address = address.trim();
+    // feeds disagree on the casing of hex addresses (CMC lowercases them,
+    // GT may use checksums), whereas base58 addresses are case-sensitive.
+    if network.is_evm() {
+        address.to_lowercase()
+    } else {
+        address.into()
+    }
+}
+
+/// Normalizes an address which isn't tied to a network (e.g., one blocked in all
+/// networks), going by whether it looks hex-encoded.
+pub fn normalize_unscoped_address(address: &str) -> String {
+    let address = address.trim();
+    if address.starts_with("0x") || address.starts_with("0X") {
+        address.to_lowercase()
+    } else {
+        address.into()
+    }
+}
+
+impl std::fmt::Display for PoolId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_{}", self.network, self.address)
+    }
+}
+
+/// Feed which surfaced a pair along with its (1-based) rank in that feed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub feed: &'static str,
+    pub rank: usize,
+}
+
 pub struct Pair {
     pub base_token: String,
     pub quote_token: String,
+    pub base_token_address: String,
+    pub quote_token_address: String,
     pub contract_address: String,
     pub mc_or_fdv: Option<f64>,
     pub liquidity: Option<f64>,
+    pub sources: Vec<Source>,
+}
+
+impl Pair {
+    /// Merges the metrics of the same pool coming from another feed, preferring
+    /// values that are already present.
+    pub fn merge(&mut self, other: Pair) {
+        if self.base_token.is_empty() {
+            self.base_token = other.base_token;
+        }
+        if self.quote_token.is_empty() {
+            self.quote_token = other.quote_token;
+        }
+        if self.base_token_address.is_empty() {
+            self.base_token_address = other.base_token_address;
+        }
+        if self.quote_token_address.is_empty() {
+            self.quote_token_address = other.quote_token_address;
+        }
+        self.mc_or_fdv = self.mc_or_fdv.or(other.mc_or_fdv);
+        self.liquidity = self.liquidity.or(other.liquidity);
+        for source in other.sources {
+            if !self.sources.iter().any(|s| s.feed == source.feed) {
+                self.sources.push(source);
+            }
+        }
+    }
 }
 
 pub trait Feed {
+    const NAME: &'static str;
+
     const DELAY: Duration;
 
     const MAX_PAGES: Option<u16> = None;
 
+    /// Number of pairs in a full page (for ranking the pairs across pages).
+    const PAGE_SIZE: usize;
+
     type Response: DeserializeOwned + Into<Vec<Pair>>;
 
     fn modify(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
@@ -92,11 +191,34 @@ impl<F> FeedClient for DefaultClient<F>
 where
     F: Feed + Send + Sync + 'static,
 {
+    fn name(&self) -> &'static str {
+        F::NAME
+    }
+
     async fn fetch_addresses(
         &self,
         network: Network,
         page: u16,
     ) -> Result<Vec<Pair>, shared::Error> {
+        let res = self.fetch(network, page).await;
+        let network = network.to_string();
+        match &res {
+            Ok(pairs) => metrics::FEED_PAIRS
+                .with_label_values(&[F::NAME, &network])
+                .inc_by(pairs.len() as u64),
+            Err(_) => metrics::FEED_ERRORS
+                .with_label_values(&[F::NAME, &network])
+                .inc(),
+        }
+        res
+    }
+}
+
+impl<F> DefaultClient<F>
+where
+    F: Feed + Send + Sync + 'static,
+{
+    async fn fetch(&self, network: Network, page: u16) -> Result<Vec<Pair>, shared::Error> {
         {
             // Avoid DoS'ing API and getting banned
             let mut time = self.last_request_time.lock().await;
@@ -127,14 +249,111 @@ where
                 if !code.is_success() {
                     log::warn!("reveived non-200 status code: {}", code);
                     return Err(shared::Error::UnexpectedStatusCode(code.as_u16(), Some(
-                        String::from_utf8_lossy(&*bytes).to_string())));
+                        String::from_utf8_lossy(&bytes).to_string())));
                 }
-                let r: F::Response = serde_json::from_slice(&*bytes).map_err(|_| {
-                    shared::Error::UnexpectedResponse(String::from_utf8_lossy(&*bytes).to_string())
+                let r: F::Response = serde_json::from_slice(&bytes).map_err(|_| {
+                    shared::Error::UnexpectedResponse(String::from_utf8_lossy(&bytes).to_string())
                 })?;
-                Ok(r.into())
+                let mut pairs: Vec<Pair> = r.into();
+                let offset = page.saturating_sub(1) as usize * F::PAGE_SIZE;
+                for (i, pair) in pairs.iter_mut().enumerate() {
+                    pair.sources.push(Source {
+                        feed: F::NAME,
+                        rank: offset + i + 1,
+                    });
+                }
+                Ok(pairs)
             }
-            Err(e) => Err(shared::Error::Http(e.into())),
+            Err(e) => Err(shared::Error::Http(e)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize_address, normalize_unscoped_address, Network, Pair, PoolId, Source};
+
+    fn pair(addr: &str, feed: &'static str, rank: usize) -> Pair {
+        Pair {
+            base_token: String::new(),
+            quote_token: String::new(),
+            base_token_address: String::new(),
+            quote_token_address: String::new(),
+            contract_address: addr.into(),
+            mc_or_fdv: None,
+            liquidity: None,
+            sources: vec![Source { feed, rank }],
+        }
+    }
+
+    #[test]
+    fn normalize_pool_id() {
+        assert_eq!(
+            PoolId::new(
+                Network::Ethereum,
+                "0x88E6A0c2dDD26FEEb64F039a2c41296FcB3f5640"
+            ),
+            PoolId::new(
+                Network::Ethereum,
+                "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"
+            ),
+        );
+        assert_ne!(
+            PoolId::new(
+                Network::Solana,
+                "7sXvhsvzmuxomqdFAU6fzxY3bukX9KkVcWWCLSw51osX"
+            ),
+            PoolId::new(
+                Network::Solana,
+                "7sxvhsvzmuxomqdfau6fzxy3bukx9kkvcwwclsw51osx"
+            ),
+        );
+        assert_ne!(
+            PoolId::new(Network::Base, "0xabc"),
+            PoolId::new(Network::Ethereum, "0xabc"),
+        );
+    }
+
+    #[test]
+    fn normalize_unscoped() {
+        assert_eq!(
+            normalize_unscoped_address(" 0x88E6A0c2dDD26FEEb64F039a2c41296FcB3f5640"),
+            normalize_address(Network::Base, "0x88e6a0c2ddd26feeb64f039a2c41296fcb3f5640"),
+        );
+        assert_eq!(
+            normalize_unscoped_address("7sXvhsvzmuxomqdFAU6fzxY3bukX9KkVcWWCLSw51osX"),
+            "7sXvhsvzmuxomqdFAU6fzxY3bukX9KkVcWWCLSw51osX",
+        );
+    }
+
+    #[test]
+    fn merge_pairs() {
+        let mut a = pair("0xabc", "cmc", 3);
+        a.liquidity = Some(5000.);
+        let mut b = pair("0xABC", "gt-top", 12);
+        b.base_token = "FOO".into();
+        b.quote_token = "WETH".into();
+        b.base_token_address = "0xdef".into();
+        b.liquidity = Some(1.);
+        b.mc_or_fdv = Some(100000.);
+        a.merge(b);
+        assert_eq!(a.base_token, "FOO");
+        assert_eq!(a.quote_token, "WETH");
+        assert_eq!(a.base_token_address, "0xdef");
+        assert_eq!(a.liquidity, Some(5000.));
+        assert_eq!(a.mc_or_fdv, Some(100000.));
+        assert_eq!(
+            a.sources,
+            vec![
+                Source {
+                    feed: "cmc",
+                    rank: 3
+                },
+                Source {
+                    feed: "gt-top",
+                    rank: 12
+                }
+            ]
+        );
+    }
+}
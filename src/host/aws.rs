@@ -1,47 +1,296 @@
-use super::Host;
-use aws_credential_types::Credentials;
+use super::{Host, RegionHealth};
+use crate::metrics;
+use aws_config::{meta::region::RegionProviderChain, sts::AssumeRoleProvider};
+use aws_credential_types::{provider::SharedCredentialsProvider, Credentials};
 use aws_sdk_lambda::{
     config::{Region, StalledStreamProtectionConfig},
     primitives::Blob,
-    Client, Config,
+    Client,
 };
+use chrono::{offset::Utc, TimeDelta};
 use futures::future;
-use serde::de::DeserializeOwned;
+use serde::{de::DeserializeOwned, Deserialize};
 
 use std::env;
+use std::sync::Mutex;
+
+/// Consecutive failures after which a region is quarantined
+const QUARANTINE_AFTER: u32 = 3;
+const QUARANTINE_MINS: i64 = 5;
+const MAX_QUARANTINE_MINS: i64 = 240;
+
+/// Environment variable (of the function) holding the version of the deployed worker
+const VERSION_VAR: &str = "WORKER_VERSION";
+const SESSION_NAME: &str = "onchain-scanner";
+/// For looking up STS when assuming a role (if there's no region in the environment)
+const DEFAULT_REGION: &str = "us-east-1";
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Regions where the function is deployed (defaults to the ones in `aws_regions.txt`)
+    #[serde(default)]
+    pub regions: Option<Vec<String>>,
+    /// Check which regions have the function deployed (with a compatible version) at startup
+    #[serde(default)]
+    pub verify_deployment: bool,
+    /// Role to assume for invoking the function
+    #[serde(default)]
+    pub role_arn: Option<String>,
+    #[serde(default)]
+    pub external_id: Option<String>,
+    /// Whether the function should parse (and compress) the candles rather than returning the
+    /// provider's response bodies
+    #[serde(default)]
+    pub encoding: shared::Encoding,
+}
 
 #[derive(Default)]
 pub struct AwsLambda {
     name: String,
     clients: Vec<(Client, String)>,
+    health: Mutex<Vec<RegionHealth>>,
+    encoding: shared::Encoding,
 }
 
 impl AwsLambda {
-    pub fn new(name: &str) -> shared::Result<Self> {
+    pub async fn new(name: &str, config: &Config) -> shared::Result<Self> {
         let mut clients = vec![];
-        let creds = if let (Some(a), Some(s)) = (
-            env::var("AWS_ACCESS_KEY").ok(),
-            env::var("AWS_SECRET_ACCESS_KEY").ok(),
-        ) {
-            Credentials::from_keys(a, s, None)
-        } else {
-            return Err(shared::Error::Config(
-                "Missing AWS access and secret keys".into(),
-            ));
+        let creds = Self::credentials(config).await?;
+        let regions = match &config.regions {
+            Some(r) => r.clone(),
+            None => shared::AWS_REGIONS.iter().map(|&r| r.into()).collect(),
         };
-        for &r in shared::AWS_REGIONS.iter() {
-            let config = Config::builder()
+        for r in regions {
+            let config = aws_sdk_lambda::Config::builder()
                 .credentials_provider(creds.clone())
-                .region(Region::new(r))
+                .region(Region::new(r.clone()))
                 .stalled_stream_protection(StalledStreamProtectionConfig::disabled())
                 .build();
-            clients.push((Client::from_conf(config), r.into()));
+            clients.push((Client::from_conf(config), r));
         }
         Ok(AwsLambda {
             name: name.into(),
+            health: Mutex::new(
+                clients
+                    .iter()
+                    .map(|(_, r)| RegionHealth {
+                        region: r.clone(),
+                        ..Default::default()
+                    })
+                    .collect(),
+            ),
             clients,
+            encoding: config.encoding,
         })
     }
+
+    /// Uses the keys from `AWS_ACCESS_KEY` and `AWS_SECRET_ACCESS_KEY` if they're set, or the
+    /// default credential chain (environment, profiles, web identity, ECS and EC2 roles) otherwise,
+    /// optionally assuming the configured role.
+    async fn credentials(config: &Config) -> shared::Result<SharedCredentialsProvider> {
+        let sdk_config = aws_config::from_env()
+            .region(RegionProviderChain::default_provider().or_else(DEFAULT_REGION))
+            .load()
+            .await;
+        let base = match (
+            env::var("AWS_ACCESS_KEY").ok(),
+            env::var("AWS_SECRET_ACCESS_KEY").ok(),
+        ) {
+            (Some(a), Some(s)) => {
+                SharedCredentialsProvider::new(Credentials::from_keys(a, s, None))
+            }
+            _ => sdk_config.credentials_provider().ok_or_else(|| {
+                shared::Error::Config("no AWS credentials found in the environment".into())
+            })?,
+        };
+        let role = match &config.role_arn {
+            Some(r) => r,
+            None => return Ok(base),
+        };
+        log::info!("assuming role {} for invoking functions", role);
+        let mut builder = AssumeRoleProvider::builder(role)
+            .session_name(SESSION_NAME)
+            .configure(&sdk_config);
+        if let Some(id) = &config.external_id {
+            builder = builder.external_id(id);
+        }
+        Ok(SharedCredentialsProvider::new(
+            builder.build_from_provider(base).await,
+        ))
+    }
+
+    /// Drops the regions where the function isn't deployed or its version is incompatible.
+    pub async fn retain_deployed(&mut self) -> shared::Result<()> {
+        let name = &self.name;
+        let deployed = future::join_all(self.clients.iter().map(|(client, region)| async move {
+            let res = match client.get_function().function_name(name).send().await {
+                Ok(r) => r,
+                Err(e) => {
+                    log::warn!(
+                        "skipping region {} (function {} not found): {:?}",
+                        region,
+                        name,
+                        e
+                    );
+                    return false;
+                }
+            };
+            let version = res
+                .configuration()
+                .and_then(|c| c.environment())
+                .and_then(|e| e.variables())
+                .and_then(|v| v.get(VERSION_VAR));
+            match version {
+                Some(v) if is_compatible(v, shared::VERSION) => true,
+                v => {
+                    log::warn!(
+                        "skipping region {} (worker version {:?} is incompatible with {})",
+                        region,
+                        v,
+                        shared::VERSION
+                    );
+                    false
+                }
+            }
+        }))
+        .await;
+
+        self.retain(deployed, "deployed")
+    }
+
+    /// Drops the regions whose workers don't speak the scanner's protocol version.
+    pub async fn handshake(&mut self) -> shared::Result<()> {
+        let (this, req) = (&*self, shared::WorkerRequest::handshake());
+        let compatible = future::join_all(this.clients.iter().map(|c| {
+            let req = &req;
+            async move {
+                match this.invoke(c, req).await {
+                    Ok(resp) => {
+                        log::info!(
+                            "worker in region {} has version {}",
+                            c.1,
+                            resp.worker_version
+                        );
+                        true
+                    }
+                    Err(e) => {
+                        log::warn!("skipping region {} (handshake failed): {}", c.1, e);
+                        false
+                    }
+                }
+            }
+        }))
+        .await;
+        self.retain(compatible, "compatible")
+    }
+
+    fn retain(&mut self, keep: Vec<bool>, what: &str) -> shared::Result<()> {
+        let mut keep = keep.into_iter();
+        self.clients.retain(|_| keep.next().unwrap_or(false));
+        log::info!(
+            "function {} is {} in {} regions",
+            self.name,
+            what,
+            self.clients.len()
+        );
+        if self.clients.is_empty() {
+            return Err(shared::Error::Config(format!(
+                "function {} isn't {} in any region",
+                self.name, what
+            )));
+        }
+        let regions = self.clients.iter().map(|(_, r)| r).collect::<Vec<_>>();
+        self.health
+            .get_mut()
+            .unwrap()
+            .retain(|h| regions.contains(&&h.region));
+        Ok(())
+    }
+
+    async fn invoke(
+        &self,
+        (client, region): &(Client, String),
+        req: &shared::WorkerRequest,
+    ) -> shared::Result<shared::WorkerResponse> {
+        metrics::HOST_INVOCATIONS.with_label_values(&[region]).inc();
+        let res = client
+            .invoke()
+            .function_name(&self.name)
+            .payload(Blob::new(serde_json::to_vec(&req)?))
+            .send()
+            .await
+            .map_err(|e| {
+                log::warn!("failed to invoke client in region {}: {:?}", region, e);
+                metrics::HOST_FAILURES.with_label_values(&[region]).inc();
+                shared::Error::AwsSdk(Box::new(e.into()))
+            })?;
+        if res.payload.is_none() {
+            return Err(shared::Error::NoPayload);
+        }
+        let payload = res.payload.unwrap().into_inner();
+        if res.status_code != 200 {
+            metrics::HOST_FAILURES.with_label_values(&[region]).inc();
+            return Err(shared::Error::UnexpectedStatusCode(
+                res.status_code as u16,
+                String::from_utf8(payload).ok(),
+            ));
+        }
+        // e.g., workers which can't deserialize the request
+        if let Some(e) = res.function_error {
+            metrics::HOST_FAILURES.with_label_values(&[region]).inc();
+            return Err(shared::Error::Runtime(format!(
+                "function error in region {} ({}): {}",
+                region,
+                e,
+                String::from_utf8_lossy(&payload)
+            )));
+        }
+        let resp: shared::WorkerResponse = serde_json::from_slice(&payload)?;
+        if let Some(e) = resp.err {
+            return Err(shared::Error::Runtime(e));
+        }
+        if resp.version != shared::PROTOCOL_VERSION {
+            return Err(shared::Error::Config(format!(
+                "worker in region {} has protocol version {} (expected {})",
+                region,
+                resp.version,
+                shared::PROTOCOL_VERSION
+            )));
+        }
+        Ok(resp)
+    }
+
+    fn record<T>(&self, idx: usize, res: &shared::Result<T>) {
+        let mut health = self.health.lock().unwrap();
+        let h = &mut health[idx];
+        match res {
+            Ok(_) => {
+                if h.quarantined_until.take().is_some() {
+                    log::info!("region {} has recovered", h.region);
+                }
+                h.successes += 1;
+                h.consecutive_failures = 0;
+            }
+            Err(e) => {
+                h.failures += 1;
+                h.consecutive_failures += 1;
+                h.last_error = Some(e.to_string());
+                if h.consecutive_failures >= QUARANTINE_AFTER {
+                    // back off exponentially while the probes keep failing
+                    let mins = (QUARANTINE_MINS
+                        << (h.consecutive_failures - QUARANTINE_AFTER).min(6))
+                    .min(MAX_QUARANTINE_MINS);
+                    log::warn!(
+                        "quarantining region {} for {} minutes after {} failures",
+                        h.region,
+                        mins,
+                        h.consecutive_failures
+                    );
+                    h.quarantined_until = Some(Utc::now() + TimeDelta::minutes(mins));
+                }
+            }
+        }
+    }
 }
 
 #[async_trait::async_trait]
@@ -49,39 +298,76 @@ impl<P: super::Provider> Host<P> for AwsLambda
 where
     P: Send + DeserializeOwned,
 {
-    fn bulk_size(&self) -> usize {
-        self.clients.len()
+    /// Regions that aren't quarantined (including those due for a probe).
+    fn regions(&self) -> Vec<String> {
+        let now = Utc::now();
+        self.health
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|h| h.quarantined_until.map(|t| t <= now).unwrap_or(true))
+            .map(|h| h.region.clone())
+            .collect()
+    }
+
+    fn health(&self) -> Vec<RegionHealth> {
+        self.health.lock().unwrap().clone()
+    }
+
+    fn encoding(&self) -> shared::Encoding {
+        self.encoding
     }
 
     async fn __trigger(
         &self,
-        request: Vec<Vec<shared::Request>>,
-    ) -> Vec<shared::Result<Vec<shared::Response>>> {
-        future::join_all(self.clients.iter().zip(request.into_iter()).map(
-            |((client, region), req)| async move {
-                let res = client
-                    .invoke()
-                    .function_name(&self.name)
-                    .payload(Blob::new(serde_json::to_vec(&req)?))
-                    .send()
-                    .await
-                    .map_err(|e| {
-                        log::warn!("failed to invoke client in region {}: {:?}", region, e);
-                        shared::Error::AwsSdk(e.into())
-                    })?;
-                if res.payload.is_none() {
-                    return Err(shared::Error::NoPayload);
+        request: Vec<(String, shared::WorkerRequest)>,
+    ) -> Vec<shared::Result<shared::WorkerResponse>> {
+        future::join_all(request.into_iter().map(|(region, req)| async move {
+            // nothing to do for throttled regions
+            if let shared::WorkerRequest::Fetch { requests, .. } = &req {
+                if requests.is_empty() {
+                    return Ok(shared::WorkerResponse::new(vec![]));
                 }
-                let payload = res.payload.unwrap().into_inner();
-                if res.status_code != 200 {
-                    return Err(shared::Error::UnexpectedStatusCode(
-                        res.status_code as u16,
-                        String::from_utf8(payload).ok(),
-                    ));
-                }
-                serde_json::from_slice(&payload).map_err(shared::Error::Serde)
-            },
-        ))
+            }
+            let idx = self
+                .clients
+                .iter()
+                .position(|(_, r)| *r == region)
+                .ok_or_else(|| shared::Error::Config(format!("unknown region {}", region)))?;
+            let res = self.invoke(&self.clients[idx], &req).await;
+            self.record(idx, &res);
+            res
+        }))
         .await
     }
 }
+
+/// Versions are compatible as long as the major (or minor, before 1.0) versions match.
+fn is_compatible(deployed: &str, ours: &str) -> bool {
+    let parts = |v: &str| {
+        v.trim_start_matches('v')
+            .split('.')
+            .map(|p| p.parse::<u32>().ok())
+            .collect::<Vec<_>>()
+    };
+    match (parts(deployed).as_slice(), parts(ours).as_slice()) {
+        ([Some(0), Some(a), ..], [Some(0), Some(b), ..]) => a == b,
+        ([Some(a), ..], [Some(b), ..]) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_compatible;
+
+    #[test]
+    fn version_compatibility() {
+        assert!(is_compatible("0.1.0", "0.1.3"));
+        assert!(is_compatible("v0.1.2", "0.1.0"));
+        assert!(!is_compatible("0.2.0", "0.1.0"));
+        assert!(is_compatible("1.4.0", "1.0.2"));
+        assert!(!is_compatible("2.0.0", "1.0.2"));
+        assert!(!is_compatible("latest", "0.1.0"));
+    }
+}
@@ -0,0 +1,321 @@
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+const fn default_max_requests_per_min() -> u16 {
+    60
+}
+const fn default_increase() -> f64 {
+    1.
+}
+const fn default_decrease() -> f64 {
+    0.5
+}
+const fn default_throttle_ratio() -> f64 {
+    0.5
+}
+const fn default_cooldown_secs() -> u64 {
+    300
+}
+const fn default_min_round_secs() -> u64 {
+    30
+}
+const fn default_max_round_secs() -> u64 {
+    600
+}
+const MAX_COOLDOWN: Duration = Duration::from_secs(3600);
+/// Time between the rounds of a region without any rounds yet (a minute and some slack)
+const INITIAL_ROUND: Duration = Duration::from_secs(65);
+/// Factor for the time between rounds after a round without 429s or 5xx
+const ROUND_DECREASE: f64 = 0.9;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Upper bound for the requests sent to a region in a round
+    #[serde(default = "default_max_requests_per_min")]
+    pub max_requests_per_min: u16,
+    /// Added to the region's limit after a round without 429s or 5xx
+    #[serde(default = "default_increase")]
+    pub increase: f64,
+    /// Multiplied with the region's limit after a round with 429s or 5xx
+    #[serde(default = "default_decrease")]
+    pub decrease: f64,
+    /// Fraction of 429s in a round after which the region is disabled for a while
+    #[serde(default = "default_throttle_ratio")]
+    pub throttle_ratio: f64,
+    /// How long a throttled region is disabled (doubled while it keeps getting throttled)
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// Lower bound for the time between rounds (shortened after rounds without 429s or 5xx)
+    #[serde(default = "default_min_round_secs")]
+    pub min_round_secs: u64,
+    /// Upper bound for the time between rounds (doubled after rounds with 429s or 5xx)
+    #[serde(default = "default_max_round_secs")]
+    pub max_round_secs: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_requests_per_min: default_max_requests_per_min(),
+            increase: default_increase(),
+            decrease: default_decrease(),
+            throttle_ratio: default_throttle_ratio(),
+            cooldown_secs: default_cooldown_secs(),
+            min_round_secs: default_min_round_secs(),
+            max_round_secs: default_max_round_secs(),
+        }
+    }
+}
+
+/// Outcome of the requests sent to a region in a round.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Outcome {
+    pub total: usize,
+    /// Requests rate limited by the upstream (429)
+    pub throttled: usize,
+    /// Requests that failed upstream (5xx) or in the host
+    pub failed: usize,
+}
+
+struct Slot {
+    limit: f64,
+    /// Time to wait before the next round
+    round: Duration,
+    disabled_until: Option<Instant>,
+    /// Consecutive rounds in which the region was throttled
+    strikes: u32,
+}
+
+/// Adapts the number of requests sent to each host region (additive increase, multiplicative decrease)
+/// and the time between rounds based on the upstream responses, disabling regions whose IPs are
+/// being throttled.
+pub struct Limiter {
+    config: Config,
+    /// Limit for regions without any rounds yet
+    initial: f64,
+    slots: HashMap<String, Slot>,
+}
+
+impl Limiter {
+    /// Starts all regions with the given number of requests per round.
+    pub fn new(config: Config, initial: u16) -> Self {
+        Limiter {
+            initial: initial.clamp(1, config.max_requests_per_min.max(1)) as f64,
+            slots: HashMap::new(),
+            config,
+        }
+    }
+
+    pub fn set_config(&mut self, config: Config, initial: u16) {
+        let max = config.max_requests_per_min.max(1);
+        self.initial = initial.clamp(1, max) as f64;
+        for slot in self.slots.values_mut() {
+            slot.limit = slot.limit.min(max as f64);
+            slot.round = slot.round.clamp(
+                Duration::from_secs(config.min_round_secs),
+                Duration::from_secs(config.max_round_secs.max(config.min_round_secs)),
+            );
+        }
+        self.config = config;
+    }
+
+    fn is_enabled(&self, region: &str, now: Instant) -> bool {
+        self.slots
+            .get(region)
+            .and_then(|s| s.disabled_until)
+            .map(|t| t <= now)
+            .unwrap_or(true)
+    }
+
+    /// Returns the number of requests the region can take in the next round (zero if disabled).
+    pub fn limit(&self, region: &str) -> usize {
+        if !self.is_enabled(region, Instant::now()) {
+            return 0;
+        }
+        self.slots
+            .get(region)
+            .map(|s| s.limit)
+            .unwrap_or(self.initial) as usize
+    }
+
+    /// Total number of requests that can be sent to the regions in the next round.
+    pub fn capacity<S: AsRef<str>>(&self, regions: &[S]) -> usize {
+        regions.iter().map(|r| self.limit(r.as_ref())).sum()
+    }
+
+    /// Time to wait between rounds, long enough for the slowest of the enabled regions.
+    pub fn round_interval<S: AsRef<str>>(&self, regions: &[S]) -> Duration {
+        let now = Instant::now();
+        regions
+            .iter()
+            .filter(|r| self.is_enabled(r.as_ref(), now))
+            .map(|r| {
+                self.slots
+                    .get(r.as_ref())
+                    .map(|s| s.round)
+                    .unwrap_or(INITIAL_ROUND)
+            })
+            .max()
+            .unwrap_or(INITIAL_ROUND)
+    }
+
+    /// Returns when the first region is enabled again, if all of them are disabled.
+    pub fn all_disabled_until<S: AsRef<str>>(&self, regions: &[S]) -> Option<Instant> {
+        let now = Instant::now();
+        if regions.iter().any(|r| self.is_enabled(r.as_ref(), now)) {
+            return None;
+        }
+        regions
+            .iter()
+            .filter_map(|r| self.slots.get(r.as_ref())?.disabled_until)
+            .min()
+    }
+
+    /// Adjusts the region's limit based on the outcome of its last round.
+    pub fn record(&mut self, region: &str, outcome: Outcome) {
+        if outcome.total == 0 {
+            return;
+        }
+        let config = &self.config;
+        let slot = self.slots.entry(region.into()).or_insert(Slot {
+            limit: self.initial,
+            round: INITIAL_ROUND,
+            disabled_until: None,
+            strikes: 0,
+        });
+        let min_round = Duration::from_secs(config.min_round_secs);
+        let max_round = Duration::from_secs(config.max_round_secs.max(config.min_round_secs));
+        if outcome.throttled > 0 || outcome.failed > 0 {
+            slot.limit = (slot.limit * config.decrease).max(1.);
+            slot.round = slot.round.saturating_mul(2).clamp(min_round, max_round);
+            log::warn!(
+                "region {} had {} throttled and {} failed requests (of {}), reducing limit to {} every {} seconds",
+                region,
+                outcome.throttled,
+                outcome.failed,
+                outcome.total,
+                slot.limit as usize,
+                slot.round.as_secs()
+            );
+        } else {
+            slot.limit = (slot.limit + config.increase).min(config.max_requests_per_min as f64);
+            slot.round = slot
+                .round
+                .mul_f64(ROUND_DECREASE)
+                .clamp(min_round, max_round);
+        }
+
+        if outcome.throttled as f64 >= outcome.total as f64 * config.throttle_ratio {
+            slot.strikes += 1;
+            let cooldown = Duration::from_secs(config.cooldown_secs)
+                .saturating_mul(2u32.saturating_pow(slot.strikes - 1))
+                .min(MAX_COOLDOWN);
+            log::warn!(
+                "disabling throttled region {} for {} seconds",
+                region,
+                cooldown.as_secs()
+            );
+            slot.disabled_until = Some(Instant::now() + cooldown);
+        } else if outcome.throttled == 0 {
+            slot.strikes = 0;
+        }
+
+        metrics::HOST_REQUEST_LIMIT
+            .with_label_values(&[region])
+            .set(slot.limit as i64);
+        metrics::HOST_THROTTLED
+            .with_label_values(&[region])
+            .inc_by(outcome.throttled as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Limiter, Outcome, INITIAL_ROUND};
+
+    use std::time::Duration;
+
+    fn outcome(total: usize, throttled: usize, failed: usize) -> Outcome {
+        Outcome {
+            total,
+            throttled,
+            failed,
+        }
+    }
+
+    #[test]
+    fn additive_increase_multiplicative_decrease() {
+        let config = Config {
+            max_requests_per_min: 32,
+            ..Default::default()
+        };
+        let regions = ["us-east-1", "eu-west-1"];
+        let mut limiter = Limiter::new(config, 30);
+        assert_eq!(limiter.capacity(&regions), 60);
+
+        limiter.record("us-east-1", outcome(30, 0, 0));
+        limiter.record("eu-west-1", outcome(30, 3, 1));
+        assert_eq!(limiter.limit("us-east-1"), 31);
+        assert_eq!(limiter.limit("eu-west-1"), 15);
+
+        for _ in 0..5 {
+            limiter.record("us-east-1", outcome(31, 0, 0));
+        }
+        limiter.record("eu-west-1", outcome(15, 0, 15));
+        assert_eq!(limiter.limit("us-east-1"), 32);
+        assert_eq!(limiter.limit("eu-west-1"), 7);
+        assert_eq!(limiter.capacity(&regions), 39);
+        assert!(limiter.all_disabled_until(&regions).is_none());
+    }
+
+    #[test]
+    fn throttled_regions_are_disabled() {
+        let mut limiter = Limiter::new(Config::default(), 30);
+        limiter.record("us-east-1", outcome(30, 20, 0));
+        assert_eq!(limiter.limit("us-east-1"), 0);
+        assert!(limiter.all_disabled_until(&["us-east-1"]).is_some());
+        assert!(limiter
+            .all_disabled_until(&["us-east-1", "eu-west-1"])
+            .is_none());
+
+        // empty rounds don't count
+        limiter.record("eu-west-1", outcome(0, 0, 0));
+        assert_eq!(limiter.limit("eu-west-1"), 30);
+    }
+
+    #[test]
+    fn round_interval_follows_outcomes() {
+        let config = Config {
+            min_round_secs: 50,
+            max_round_secs: 200,
+            ..Default::default()
+        };
+        let regions = ["us-east-1", "eu-west-1"];
+        let mut limiter = Limiter::new(config, 30);
+        assert_eq!(limiter.round_interval(&regions), INITIAL_ROUND);
+
+        // clean rounds shorten the wait down to the minimum
+        for _ in 0..10 {
+            for r in regions {
+                limiter.record(r, outcome(30, 0, 0));
+            }
+        }
+        assert_eq!(limiter.round_interval(&regions), Duration::from_secs(50));
+
+        // the slowest region sets the pace, backing off up to the maximum
+        limiter.record("eu-west-1", outcome(30, 1, 2));
+        assert_eq!(limiter.round_interval(&regions), Duration::from_secs(100));
+        for _ in 0..3 {
+            limiter.record("eu-west-1", outcome(10, 0, 1));
+        }
+        assert_eq!(limiter.round_interval(&regions), Duration::from_secs(200));
+
+        // disabled regions don't hold back the others
+        limiter.record("eu-west-1", outcome(10, 10, 0));
+        assert_eq!(limiter.round_interval(&regions), Duration::from_secs(50));
+    }
+}
@@ -1,47 +1,144 @@
 use super::provider::Provider;
+use crate::metrics;
+use chrono::{offset::Utc, DateTime};
+use serde::Serialize;
+use shared::ohlcv::{Bars, OHLCVList};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 mod aws;
 mod azure;
 mod gcloud;
+pub mod limiter;
 
-pub use self::aws::AwsLambda;
+pub use self::aws::{AwsLambda, Config as AwsConfig};
+
+/// Ids for correlating the responses of the workers with the requests
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Invocation statistics of a host region.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RegionHealth {
+    pub region: String,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    /// Set while the region is left out of batches (it's probed again afterwards)
+    pub quarantined_until: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
 
 #[async_trait::async_trait]
 pub trait Host<P: Provider> {
-    fn bulk_size(&self) -> usize;
+    /// Names of the healthy regions (requests are batched per region).
+    fn regions(&self) -> Vec<String>;
+
+    fn bulk_size(&self) -> usize {
+        self.regions().len()
+    }
+
+    fn health(&self) -> Vec<RegionHealth> {
+        vec![]
+    }
+
+    /// How the workers should return the candles.
+    fn encoding(&self) -> shared::Encoding {
+        shared::Encoding::Raw
+    }
 
     async fn __trigger(
         &self,
-        request: Vec<Vec<shared::Request>>,
-    ) -> Vec<shared::Result<Vec<shared::Response>>>;
+        request: Vec<(String, shared::WorkerRequest)>,
+    ) -> Vec<shared::Result<shared::WorkerResponse>>;
+
+    /// Invokes the host in each region with its batch of requests (asking the workers to
+    /// return only the signals if `analyze` is set).
+    async fn trigger(
+        &self,
+        request: Vec<(String, Vec<shared::Request>)>,
+        analyze: bool,
+    ) -> Vec<Vec<shared::Result<Bars>>> {
+        let encoding = self.encoding();
+        let mut batches = vec![];
+        let mut envelopes = vec![];
+        for (region, requests) in request {
+            let tagged = requests
+                .into_iter()
+                .map(|r| shared::Tagged {
+                    id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+                    inner: r,
+                })
+                .collect::<Vec<_>>();
+            batches.push((
+                region.clone(),
+                tagged
+                    .iter()
+                    .map(|r| (r.id, r.inner.provider))
+                    .collect::<Vec<_>>(),
+            ));
+            envelopes.push((
+                region,
+                shared::WorkerRequest::fetch(tagged, encoding, analyze),
+            ));
+        }
 
-    async fn trigger(&self, request: Vec<Vec<shared::Request>>) -> Vec<Vec<shared::Result<P>>> {
-        // assert_eq!(request.len(), self.bulk_size()); // ensure that requests match max size for this host
-        let resp_len = request.iter().map(|r| r.len()).collect::<Vec<_>>();
-        self.__trigger(request)
+        self.__trigger(envelopes)
             .await
             .into_iter()
-            .zip(resp_len.into_iter())
-            .map(|(res, l)| match res {
-                Ok(resp) => resp
-                    .into_iter()
-                    .map(|r| {
-                        if let Some(e) = r.err {
-                            return Err(shared::Error::Runtime(e));
-                        }
-                        match (r.status, r.body) {
-                            (Some(200), Some(b)) => {
-                                serde_json::from_str(&b).map_err(shared::Error::Serde)
+            .zip(batches.into_iter())
+            .map(|(res, (region, batch))| match res {
+                Ok(resp) => {
+                    // responses are matched by their ids (the worker may reorder or drop them)
+                    let mut responses = resp
+                        .responses
+                        .into_iter()
+                        .map(|r| (r.id, r.inner))
+                        .collect::<HashMap<_, _>>();
+                    batch
+                        .into_iter()
+                        .map(|(id, provider)| {
+                            let r = responses.remove(&id).ok_or_else(|| {
+                                shared::Error::UnexpectedResponse(format!(
+                                    "missing response for request {}",
+                                    id
+                                ))
+                            })?;
+                            metrics::WORKER_FETCH_DURATION
+                                .with_label_values(&[&region])
+                                .observe(r.elapsed_ms as f64 / 1000.);
+                            metrics::WORKER_FETCH_RETRIES
+                                .with_label_values(&[&region])
+                                .inc_by(r.retries as u64);
+                            if let Some(e) = r.err {
+                                return Err(shared::Error::Runtime(e));
                             }
-                            (Some(s), b) => Err(shared::Error::UnexpectedStatusCode(s, b)),
-                            _ => unreachable!(),
-                        }
-                    })
-                    .collect::<Vec<_>>(),
+                            match (r.status, r.signals, r.candles, r.body) {
+                                (Some(200), Some(s), _, _) => Ok(Bars::Signals(s)),
+                                (Some(200), None, Some(c), _) => {
+                                    OHLCVList::try_from(shared::Candles::decode(&c)?)
+                                        .map(Bars::Candles)
+                                }
+                                (Some(200), None, None, Some(b)) => P::parse(provider, &b)
+                                    .and_then(|p| p.ohlcv_data())
+                                    .map(Bars::Candles),
+                                (Some(s), _, _, b) => {
+                                    Err(shared::Error::UnexpectedStatusCode(s, b))
+                                }
+                                (None, _, _, _) => Err(shared::Error::UnexpectedResponse(format!(
+                                    "no status or error in response for request {}",
+                                    id
+                                ))),
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                }
                 Err(e) => {
                     let e = Arc::new(e);
-                    (0..l).map(|_| Err(e.clone().into())).collect::<Vec<_>>()
+                    batch
+                        .iter()
+                        .map(|_| Err(e.clone().into()))
+                        .collect::<Vec<_>>()
                 }
             })
             .collect()
@@ -1,19 +1,66 @@
 use axum::{
-    extract::{Query, Request, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{FromRef, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
     middleware::{self, Next},
     response::IntoResponse,
+    Json,
 };
 use axum::{routing, Router};
+use chrono::TimeDelta;
+use strum::VariantArray;
 
 use std::collections::HashMap;
 
-use super::storage::Storage;
+use super::analyzer::{Analyzer, Report};
+use super::control::{self, Control};
+use super::feed::{normalize_address, normalize_unscoped_address, Network};
+use super::host::RegionHealth;
+use super::interaction::{self, Interaction};
+use super::metrics;
+use super::provider::MultiProvider;
+use super::storage::{BlockKind, Blocked, Storage, Watched};
+
+type PoolAnalyzer = Analyzer<MultiProvider>;
+
+const DEFAULT_PAGE_LIMIT: usize = 100;
 
 lazy_static::lazy_static! {
     pub static ref AUTH_KEY: String = std::env::var("AUTH_KEY").expect("AUTH_KEY unset");
 }
 
+#[derive(Clone)]
+struct AppState {
+    storage: Storage,
+    control: Control,
+    analyzer: PoolAnalyzer,
+}
+
+impl FromRef<AppState> for Storage {
+    fn from_ref(state: &AppState) -> Self {
+        state.storage.clone()
+    }
+}
+
+impl FromRef<AppState> for Control {
+    fn from_ref(state: &AppState) -> Self {
+        state.control.clone()
+    }
+}
+
+impl FromRef<AppState> for PoolAnalyzer {
+    fn from_ref(state: &AppState) -> Self {
+        state.analyzer.clone()
+    }
+}
+
+#[derive(serde::Serialize)]
+struct BlockedPage {
+    total: u64,
+    offset: usize,
+    entries: Vec<Blocked>,
+}
+
 pub struct Handler;
 
 impl Handler {
@@ -33,19 +80,51 @@ impl Handler {
         Ok(next.run(Request::from_parts(parts, body)).await)
     }
 
+    fn block_kind(params: &HashMap<String, String>) -> Result<BlockKind, StatusCode> {
+        match params.get("kind").map(|k| k.as_str()) {
+            None | Some("pool") => Ok(BlockKind::Pool),
+            Some("token") => Ok(BlockKind::Token),
+            Some(_) => Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    fn network(params: &HashMap<String, String>) -> Result<Option<Network>, StatusCode> {
+        match params.get("network") {
+            Some(n) => Network::VARIANTS
+                .iter()
+                .find(|v| v.to_string() == *n)
+                .map(|n| Some(*n))
+                .ok_or(StatusCode::BAD_REQUEST),
+            None => Ok(None),
+        }
+    }
+
     async fn block_address(
         Query(params): Query<HashMap<String, String>>,
         State(state): State<Storage>,
     ) -> Result<String, StatusCode> {
         if let Some(addr) = params.get("addr") {
-            if let Err(e) = state.block_address(addr) {
+            let network = Self::network(&params)?;
+            let addr = match network {
+                Some(n) => normalize_address(n, addr),
+                None => normalize_unscoped_address(addr),
+            };
+            let mut blocked = Blocked::new(Self::block_kind(&params)?, &addr);
+            blocked.network = network.map(|n| n.to_string());
+            blocked.reason = params.get("reason").cloned();
+            blocked.added_by = params.get("by").cloned();
+            if let Some(h) = params.get("ttl_hours") {
+                let h = h.parse::<i64>().map_err(|_| StatusCode::BAD_REQUEST)?;
+                blocked.expires_at = Some(blocked.added_at + TimeDelta::hours(h));
+            }
+            if let Err(e) = state.block(&blocked) {
                 log::error!("failed to block address: {}", e);
                 return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
-            log::info!("blocked address: {}", addr);
+            log::info!("blocked {:?} address: {}", blocked.kind, addr);
             Ok("{}".into())
         } else {
-            return Err(StatusCode::BAD_REQUEST);
+            Err(StatusCode::BAD_REQUEST)
         }
     }
 
@@ -54,27 +133,295 @@ impl Handler {
         State(state): State<Storage>,
     ) -> Result<String, StatusCode> {
         if let Some(addr) = params.get("addr") {
-            if let Err(e) = state.unblock_address(addr) {
+            let network = Self::network(&params)?;
+            let addr = match network {
+                Some(n) => normalize_address(n, addr),
+                None => normalize_unscoped_address(addr),
+            };
+            let network = network.map(|n| n.to_string());
+            if let Err(e) = state.unblock(Self::block_kind(&params)?, network.as_deref(), &addr) {
                 log::error!("failed to unblock address: {}", e);
                 return Err(StatusCode::INTERNAL_SERVER_ERROR);
             }
             log::info!("unblocked address: {}", addr);
             Ok("{}".into())
         } else {
-            return Err(StatusCode::BAD_REQUEST);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+
+    async fn list_blocked(
+        Query(params): Query<HashMap<String, String>>,
+        State(state): State<Storage>,
+    ) -> Result<Json<BlockedPage>, StatusCode> {
+        let parse = |k: &str, default| match params.get(k) {
+            Some(v) => v.parse::<usize>().map_err(|_| StatusCode::BAD_REQUEST),
+            None => Ok(default),
+        };
+        let offset = parse("offset", 0)?;
+        let limit = parse("limit", DEFAULT_PAGE_LIMIT)?;
+        match state.blocked(offset, limit) {
+            Ok((total, entries)) => Ok(Json(BlockedPage {
+                total,
+                offset,
+                entries,
+            })),
+            Err(e) => {
+                log::error!("failed to list blocked addresses: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    async fn export_blocked(
+        State(state): State<Storage>,
+    ) -> Result<Json<Vec<Blocked>>, StatusCode> {
+        match state.blocked(0, usize::MAX) {
+            Ok((_, entries)) => Ok(Json(entries)),
+            Err(e) => {
+                log::error!("failed to export blocked addresses: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    async fn import_blocked(
+        State(state): State<Storage>,
+        Json(entries): Json<Vec<Blocked>>,
+    ) -> Result<String, StatusCode> {
+        if let Err(e) = state.block_all(&entries) {
+            log::error!("failed to import blocked addresses: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        log::info!("imported {} blocked addresses", entries.len());
+        Ok("{}".into())
+    }
+
+    async fn scan(
+        Query(params): Query<HashMap<String, String>>,
+        State(control): State<Control>,
+    ) -> Result<String, StatusCode> {
+        let network = params
+            .get("network")
+            .and_then(|n| Network::VARIANTS.iter().find(|v| v.to_string() == *n))
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        log::info!("requested scan for network {}", network);
+        control.request_scan(*network);
+        Ok("{}".into())
+    }
+
+    async fn pause(State(control): State<Control>) -> String {
+        log::info!("pausing runner");
+        control.pause();
+        "{}".into()
+    }
+
+    async fn resume(State(control): State<Control>) -> String {
+        log::info!("resuming runner");
+        control.resume();
+        "{}".into()
+    }
+
+    async fn state(State(control): State<Control>) -> Json<control::State> {
+        Json(control.state())
+    }
+
+    async fn hosts(State(analyzer): State<PoolAnalyzer>) -> Json<Vec<RegionHealth>> {
+        Json(analyzer.hosts.iter().flat_map(|h| h.health()).collect())
+    }
+
+    async fn reload(State(control): State<Control>) -> String {
+        log::info!("requested config reload");
+        control.request_reload();
+        "{}".into()
+    }
+
+    fn pool_params(params: &HashMap<String, String>) -> Result<(Network, String), StatusCode> {
+        let network = params
+            .get("network")
+            .and_then(|n| Network::VARIANTS.iter().find(|v| v.to_string() == *n))
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        let pool = params.get("pool").ok_or(StatusCode::BAD_REQUEST)?;
+        Ok((*network, normalize_address(*network, pool)))
+    }
+
+    async fn watch(
+        Query(params): Query<HashMap<String, String>>,
+        State(state): State<Storage>,
+    ) -> Result<String, StatusCode> {
+        let (network, pool) = Self::pool_params(&params)?;
+        let watched = Watched {
+            network: network.to_string(),
+            pool_address: pool,
+            note: params.get("note").cloned(),
+            added_at: chrono::Utc::now(),
+        };
+        if let Err(e) = state.watch(&watched) {
+            log::error!("failed to watch pool: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        log::info!("watching pool: {} ({})", watched.pool_address, network);
+        Ok("{}".into())
+    }
+
+    async fn unwatch(
+        Query(params): Query<HashMap<String, String>>,
+        State(state): State<Storage>,
+    ) -> Result<String, StatusCode> {
+        let (network, pool) = Self::pool_params(&params)?;
+        match state.unwatch(&network.to_string(), &pool) {
+            Ok(true) => {
+                log::info!("unwatched pool: {} ({})", pool, network);
+                Ok("{}".into())
+            }
+            Ok(false) => Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                log::error!("failed to unwatch pool: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    async fn watchlist(
+        Query(params): Query<HashMap<String, String>>,
+        State(state): State<Storage>,
+    ) -> Result<Json<Vec<Watched>>, StatusCode> {
+        match state.watchlist(params.get("network").map(|n| n.as_str())) {
+            Ok(w) => Ok(Json(w)),
+            Err(e) => {
+                log::error!("failed to list watchlist: {}", e);
+                Err(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+
+    async fn analyze(
+        Path((network, pool)): Path<(String, String)>,
+        Query(params): Query<HashMap<String, String>>,
+        State(analyzer): State<PoolAnalyzer>,
+    ) -> Result<Json<Report>, (StatusCode, String)> {
+        let network = Network::VARIANTS
+            .iter()
+            .find(|n| n.to_string() == network)
+            .ok_or((StatusCode::BAD_REQUEST, "unknown network".into()))?;
+        let notify = params.get("notify").map(|v| v == "true").unwrap_or(false);
+        let pool = normalize_address(*network, &pool);
+        match analyzer.analyze(&network.to_string(), &pool, notify).await {
+            Ok(report) => Ok(Json(report)),
+            Err(e) => {
+                log::error!("failed to analyze pool {} ({}): {}", pool, network, e);
+                let status = match e {
+                    shared::Error::UnexpectedStatusCode(404, _) => StatusCode::NOT_FOUND,
+                    shared::Error::Config(_) => StatusCode::BAD_REQUEST,
+                    _ => StatusCode::BAD_GATEWAY,
+                };
+                Err((status, e.to_string()))
+            }
         }
     }
 
-    pub async fn serve(addr: &str, storage: Storage) {
+    /// Alive while the runner is running (its state is served by `/state` instead).
+    async fn healthz(State(control): State<Control>) -> Result<String, (StatusCode, String)> {
+        if !control.is_running() {
+            return Err((StatusCode::SERVICE_UNAVAILABLE, "runner stopped".into()));
+        }
+        Ok("{}".into())
+    }
+
+    /// Ready if the runner is alive and storage is readable.
+    async fn readyz(
+        State(storage): State<Storage>,
+        State(control): State<Control>,
+    ) -> Result<String, (StatusCode, String)> {
+        if !control.is_running() {
+            return Err((StatusCode::SERVICE_UNAVAILABLE, "runner stopped".into()));
+        }
+        if let Err(e) = storage.ping() {
+            log::error!("storage unavailable: {}", e);
+            return Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "storage unavailable".into(),
+            ));
+        }
+        Ok("{}".into())
+    }
+
+    async fn metrics() -> Result<String, StatusCode> {
+        metrics::gather().map_err(|e| {
+            log::error!("failed to encode metrics: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+    }
+
+    async fn discord_interaction(
+        headers: HeaderMap,
+        State(storage): State<Storage>,
+        State(analyzer): State<PoolAnalyzer>,
+        body: Bytes,
+    ) -> Result<Json<serde_json::Value>, StatusCode> {
+        let key = interaction::PUBLIC_KEY
+            .as_ref()
+            .ok_or(StatusCode::NOT_FOUND)?;
+        let header = |h: &str| headers.get(h).and_then(|v| v.to_str().ok());
+        match (
+            header("X-Signature-Ed25519"),
+            header("X-Signature-Timestamp"),
+        ) {
+            (Some(sig), Some(ts))
+                if interaction::is_recent(ts, chrono::Utc::now())
+                    && interaction::verify(key, sig, ts, &body) => {}
+            _ => return Err(StatusCode::UNAUTHORIZED),
+        }
+        let interaction: Interaction =
+            serde_json::from_slice(&body).map_err(|_| StatusCode::BAD_REQUEST)?;
+        Ok(Json(interaction::handle(interaction, &storage, &analyzer)))
+    }
+
+    /// Serves the API until the `shutdown` future resolves (and the in-flight requests are done).
+    pub async fn serve(
+        addr: &str,
+        storage: Storage,
+        control: Control,
+        analyzer: PoolAnalyzer,
+        shutdown: impl std::future::Future<Output = ()> + Send + 'static,
+    ) {
         let app = Router::new()
+            .route("/block", routing::get(Self::list_blocked))
             .route("/block", routing::put(Self::block_address))
             .route("/block", routing::delete(Self::unblock_address))
+            .route("/block/export", routing::get(Self::export_blocked))
+            .route("/block/import", routing::post(Self::import_blocked))
+            .route("/scan", routing::post(Self::scan))
+            .route("/pause", routing::post(Self::pause))
+            .route("/resume", routing::post(Self::resume))
+            .route("/state", routing::get(Self::state))
+            .route("/reload", routing::post(Self::reload))
+            .route("/hosts", routing::get(Self::hosts))
+            .route("/watch", routing::get(Self::watchlist))
+            .route("/watch", routing::put(Self::watch))
+            .route("/watch", routing::delete(Self::unwatch))
+            .route("/analyze/:network/:pool", routing::get(Self::analyze))
             .layer(middleware::from_fn(Self::auth_middleware))
-            .with_state(storage);
+            .route("/healthz", routing::get(Self::healthz))
+            .route("/readyz", routing::get(Self::readyz))
+            .route("/metrics", routing::get(Self::metrics))
+            // discord signs its requests instead
+            .route(
+                "/discord/interactions",
+                routing::post(Self::discord_interaction),
+            )
+            .with_state(AppState {
+                storage,
+                control,
+                analyzer,
+            });
         let listener = tokio::net::TcpListener::bind(addr)
             .await
             .expect("binding address");
         log::info!("Listening to {}", addr);
-        axum::serve(listener, app).await.expect("running server");
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .expect("running server");
     }
 }
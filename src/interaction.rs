@@ -0,0 +1,410 @@
+use chrono::{offset::Utc, DateTime, TimeDelta};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use strum::VariantArray;
+
+use std::collections::HashMap;
+
+use super::analyzer::{Analyzer, Report};
+use super::feed::{normalize_address, normalize_unscoped_address, Network};
+use super::provider::Provider;
+use super::storage::{BlockKind, Blocked, Storage, Watched};
+
+const DISCORD_API: &str = "https://discord.com/api/v10";
+
+// https://discord.com/developers/docs/interactions/receiving-and-responding
+const PING: u8 = 1;
+const APPLICATION_COMMAND: u8 = 2;
+const PONG: u8 = 1;
+const CHANNEL_MESSAGE: u8 = 4;
+const DEFERRED_CHANNEL_MESSAGE: u8 = 5;
+
+/// Maximum age of a signed request (older ones could be replays).
+const MAX_TIMESTAMP_AGE_SECS: i64 = 300;
+
+lazy_static::lazy_static! {
+    /// Public key of the Discord application (interactions are disabled if unset)
+    pub static ref PUBLIC_KEY: Option<VerifyingKey> = std::env::var("DISCORD_PUBLIC_KEY")
+        .ok()
+        .map(|k| parse_public_key(&k).expect("invalid DISCORD_PUBLIC_KEY"));
+    /// Roles allowed to change the blocklist and the watchlist (comma-separated role IDs,
+    /// nobody if unset)
+    static ref CURATOR_ROLES: Vec<String> = std::env::var("DISCORD_CURATOR_ROLES")
+        .map(|r| parse_roles(&r))
+        .unwrap_or_default();
+}
+
+fn parse_roles(roles: &str) -> Vec<String> {
+    roles
+        .split(',')
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect()
+}
+
+fn parse_public_key(key: &str) -> Option<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(key.trim()).ok()?.try_into().ok()?;
+    VerifyingKey::from_bytes(&bytes).ok()
+}
+
+/// Verifies the `X-Signature-Ed25519` of the request (signed over timestamp and body).
+pub fn verify(key: &VerifyingKey, signature: &str, timestamp: &str, body: &[u8]) -> bool {
+    let sig: [u8; 64] = match hex::decode(signature).ok().and_then(|s| s.try_into().ok()) {
+        Some(s) => s,
+        None => return false,
+    };
+    let mut msg = timestamp.as_bytes().to_vec();
+    msg.extend_from_slice(body);
+    key.verify(&msg, &Signature::from_bytes(&sig)).is_ok()
+}
+
+/// Whether the `X-Signature-Timestamp` (in seconds) is recent enough, so that a captured
+/// request can't be replayed later on.
+pub fn is_recent(timestamp: &str, now: DateTime<Utc>) -> bool {
+    match timestamp.parse::<i64>() {
+        Ok(t) => (now.timestamp() - t).abs() <= MAX_TIMESTAMP_AGE_SECS,
+        Err(_) => false,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Interaction {
+    id: String,
+    #[serde(rename = "type")]
+    kind: u8,
+    application_id: String,
+    token: String,
+    #[serde(default)]
+    data: Option<CommandData>,
+    /// Set for commands in guilds
+    #[serde(default)]
+    member: Option<Member>,
+    /// Set for commands in DMs
+    #[serde(default)]
+    user: Option<User>,
+}
+
+#[derive(Deserialize)]
+struct CommandData {
+    name: String,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+}
+
+#[derive(Deserialize)]
+struct CommandOption {
+    name: String,
+    value: Value,
+}
+
+#[derive(Deserialize)]
+struct Member {
+    user: User,
+    /// Role IDs of the member
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct User {
+    username: String,
+}
+
+impl Interaction {
+    fn options(&self) -> HashMap<&str, String> {
+        self.data
+            .iter()
+            .flat_map(|d| d.options.iter())
+            .map(|o| {
+                let v = match &o.value {
+                    Value::String(s) => s.clone(),
+                    v => v.to_string(),
+                };
+                (o.name.as_str(), v)
+            })
+            .collect()
+    }
+
+    /// Whether the member has any of the roles (never for DMs).
+    fn has_any_role(&self, roles: &[String]) -> bool {
+        self.member
+            .as_ref()
+            .map(|m| m.roles.iter().any(|r| roles.contains(r)))
+            .unwrap_or(false)
+    }
+
+    fn username(&self) -> Option<&str> {
+        self.member
+            .as_ref()
+            .map(|m| &m.user)
+            .or(self.user.as_ref())
+            .map(|u| u.username.as_str())
+    }
+}
+
+fn message(content: &str) -> Value {
+    json!({ "type": CHANNEL_MESSAGE, "data": { "content": content } })
+}
+
+fn network(options: &HashMap<&str, String>) -> Result<Option<Network>, String> {
+    match options.get("network") {
+        Some(n) => Network::VARIANTS
+            .iter()
+            .find(|v| v.to_string() == *n)
+            .map(|n| Some(*n))
+            .ok_or_else(|| format!("unknown network: {}", n)),
+        None => Ok(None),
+    }
+}
+
+fn address(
+    options: &HashMap<&str, String>,
+    key: &str,
+    network: Option<Network>,
+) -> Result<String, String> {
+    let addr = options.get(key).ok_or_else(|| format!("missing {}", key))?;
+    Ok(match network {
+        Some(n) => normalize_address(n, addr),
+        None => normalize_unscoped_address(addr),
+    })
+}
+
+fn block_kind(options: &HashMap<&str, String>) -> Result<BlockKind, String> {
+    match options.get("kind").map(|k| k.as_str()) {
+        None | Some("pool") => Ok(BlockKind::Pool),
+        Some("token") => Ok(BlockKind::Token),
+        Some(k) => Err(format!("unknown kind: {}", k)),
+    }
+}
+
+/// Handles the (verified) interaction and returns the immediate response.
+pub fn handle<P>(interaction: Interaction, storage: &Storage, analyzer: &Analyzer<P>) -> Value
+where
+    P: Provider + Send + Sync + 'static,
+{
+    let command = match (interaction.kind, &interaction.data) {
+        (PING, _) => return json!({ "type": PONG }),
+        (APPLICATION_COMMAND, Some(data)) => data.name.clone(),
+        (kind, _) => {
+            log::warn!("ignoring interaction of type {}", kind);
+            return message("unsupported interaction");
+        }
+    };
+    log::info!(
+        "received command /{} from {}",
+        command,
+        interaction.username().unwrap_or("unknown")
+    );
+    let res = match command.as_str() {
+        "block" | "unblock" | "watch" if !interaction.has_any_role(&CURATOR_ROLES) => {
+            log::warn!(
+                "denied /{} to {}",
+                command,
+                interaction.username().unwrap_or("unknown")
+            );
+            Err("only curators can change the blocklist and the watchlist".into())
+        }
+        "block" => block(&interaction, storage),
+        "unblock" => unblock(&interaction, storage),
+        "watch" => watch(&interaction, storage),
+        "analyze" => return analyze(interaction, analyzer.clone()),
+        c => Err(format!("unknown command: {}", c)),
+    };
+    message(&res.unwrap_or_else(|e| format!("error: {}", e)))
+}
+
+fn block(interaction: &Interaction, storage: &Storage) -> Result<String, String> {
+    let options = interaction.options();
+    let network = network(&options)?;
+    let mut blocked = Blocked::new(
+        block_kind(&options)?,
+        &address(&options, "address", network)?,
+    );
+    blocked.network = network.map(|n| n.to_string());
+    blocked.reason = options.get("reason").cloned();
+    blocked.added_by = interaction.username().map(|u| format!("discord:{}", u));
+    if let Some(h) = options.get("ttl_hours") {
+        let h = h
+            .parse::<i64>()
+            .map_err(|_| format!("invalid ttl_hours: {}", h))?;
+        blocked.expires_at = Some(Utc::now() + TimeDelta::hours(h));
+    }
+    storage.block(&blocked).map_err(|e| e.to_string())?;
+    log::info!("blocked {:?} address: {}", blocked.kind, blocked.address);
+    Ok(format!("blocked `{}`", blocked.address))
+}
+
+fn unblock(interaction: &Interaction, storage: &Storage) -> Result<String, String> {
+    let options = interaction.options();
+    let network = network(&options)?;
+    let addr = address(&options, "address", network)?;
+    let network = network.map(|n| n.to_string());
+    match storage.unblock(block_kind(&options)?, network.as_deref(), &addr) {
+        Ok(true) => {
+            log::info!("unblocked address: {}", addr);
+            Ok(format!("unblocked `{}`", addr))
+        }
+        Ok(false) => Ok(format!("`{}` isn't blocked", addr)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn watch(interaction: &Interaction, storage: &Storage) -> Result<String, String> {
+    let options = interaction.options();
+    let network = network(&options)?.ok_or("missing network")?;
+    let watched = Watched {
+        network: network.to_string(),
+        pool_address: address(&options, "pool", Some(network))?,
+        note: options.get("note").cloned(),
+        added_at: Utc::now(),
+    };
+    storage.watch(&watched).map_err(|e| e.to_string())?;
+    log::info!("watching pool: {} ({})", watched.pool_address, network);
+    Ok(format!("watching `{}` ({})", watched.pool_address, network))
+}
+
+/// Defers the response and posts the analysis as a follow-up (since it takes a while).
+fn analyze<P>(interaction: Interaction, analyzer: Analyzer<P>) -> Value
+where
+    P: Provider + Send + Sync + 'static,
+{
+    let parsed = {
+        let options = interaction.options();
+        network(&options)
+            .and_then(|n| n.ok_or_else(|| "missing network".into()))
+            .and_then(|n| Ok((n, address(&options, "pool", Some(n))?)))
+    };
+    let (network, pool) = match parsed {
+        Ok(v) => v,
+        Err(e) => return message(&format!("error: {}", e)),
+    };
+
+    tokio::task::spawn(async move {
+        let content = match analyzer.analyze(&network.to_string(), &pool, false).await {
+            Ok(report) => summary(&pool, &report),
+            Err(e) => format!("failed to analyze `{}`: {}", pool, e),
+        };
+        let url = format!(
+            "{}/webhooks/{}/{}/messages/@original",
+            DISCORD_API, interaction.application_id, interaction.token
+        );
+        // the URL has the interaction's token
+        log::info!("posting analysis for interaction {}", interaction.id);
+        let res = reqwest::Client::new()
+            .patch(&url)
+            .json(&json!({ "content": content }))
+            .send()
+            .await;
+        match res {
+            Ok(r) if !r.status().is_success() => {
+                log::error!("failed to post follow-up: status {}", r.status())
+            }
+            Err(e) => log::error!("failed to post follow-up: {}", e),
+            _ => (),
+        }
+    });
+    json!({ "type": DEFERRED_CHANNEL_MESSAGE })
+}
+
+fn summary(pool: &str, report: &Report) -> String {
+    let mut msg = format!("`{}` ({:?})", pool, report.provider);
+    for (name, analysis) in [("1D", &report.daily), ("3D", &report.three_day)] {
+        let a = match analysis {
+            Some(a) => a,
+            None => {
+                msg.push_str(&format!("\n{}: not enough candles", name));
+                continue;
+            }
+        };
+        let date = |idx: usize| a.ohlcv[idx].timestamp.format("%Y-%m-%d").to_string();
+        msg.push_str(&format!(
+            "\n{}: {} candles, close {}",
+            name,
+            a.ohlcv.len(),
+            a.ohlcv.last().map(|c| c.close).unwrap_or_default()
+        ));
+        if let Some(b) = a.range_high_breaks.last() {
+            msg.push_str(&format!(", range high break at {}", date(b.idx)));
+        }
+        if let Some(b) = a.range_low_breaks.last() {
+            msg.push_str(&format!(", range low break at {}", date(b.idx)));
+        }
+        if let Some(e) = a.bullish_engulfing.last() {
+            msg.push_str(&format!(", bullish engulfing at {}", date(e.idx)));
+        }
+        if let Some(e) = a.bearish_engulfing.last() {
+            msg.push_str(&format!(", bearish engulfing at {}", date(e.idx)));
+        }
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_recent, parse_public_key, parse_roles, verify, Interaction};
+    use chrono::{offset::Utc, TimeZone};
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+
+    #[test]
+    fn verify_signature() {
+        let signing = SigningKey::from_bytes(&[7; 32]);
+        let key = parse_public_key(&hex::encode(signing.verifying_key().as_bytes())).unwrap();
+        let (timestamp, body) = ("1718000000", br#"{"type":1}"#);
+        let sig = signing.sign(&[timestamp.as_bytes(), body].concat());
+        let sig = hex::encode(sig.to_bytes());
+
+        assert!(verify(&key, &sig, timestamp, body));
+        assert!(!verify(&key, &sig, "1718000001", body));
+        assert!(!verify(&key, &sig, timestamp, br#"{"type":2}"#));
+        assert!(!verify(&key, "deadbeef", timestamp, body));
+
+        let now = Utc.timestamp_opt(1718000000 + 60, 0).unwrap();
+        assert!(is_recent(timestamp, now));
+        assert!(!is_recent(timestamp, now + chrono::TimeDelta::minutes(5)));
+        assert!(!is_recent("not a timestamp", now));
+    }
+
+    #[test]
+    fn command_options() {
+        let interaction: Interaction = serde_json::from_value(json!({
+            "id": "456",
+            "type": 2,
+            "application_id": "123",
+            "token": "abc",
+            "member": { "user": { "username": "alice" }, "roles": ["10", "11"] },
+            "data": {
+                "name": "block",
+                "options": [
+                    { "name": "address", "value": "0xABC", "type": 3 },
+                    { "name": "ttl_hours", "value": 24, "type": 4 }
+                ]
+            }
+        }))
+        .unwrap();
+        let options = interaction.options();
+        assert_eq!(options["address"], "0xABC");
+        assert_eq!(options["ttl_hours"], "24");
+        assert_eq!(interaction.username(), Some("alice"));
+    }
+
+    #[test]
+    fn curator_roles() {
+        // commands in guilds come with the member and in DMs with the user
+        let interaction = |key: &str, value: serde_json::Value| -> Interaction {
+            let mut v = json!({ "id": "456", "type": 2, "application_id": "123", "token": "abc" });
+            v[key] = value;
+            serde_json::from_value(v).unwrap()
+        };
+        let curators = parse_roles(" 11, ,12");
+        assert_eq!(curators, vec!["11", "12"]);
+
+        let member = json!({ "user": { "username": "alice" }, "roles": ["10", "11"] });
+        assert!(interaction("member", member).has_any_role(&curators));
+        let member = json!({ "user": { "username": "bob" }, "roles": ["10"] });
+        assert!(!interaction("member", member).has_any_role(&curators));
+        let user = json!({ "username": "carol" });
+        assert!(!interaction("user", user).has_any_role(&curators));
+    }
+}
@@ -1,28 +1,34 @@
 #![recursion_limit = "512"]
 
+pub mod analyzer;
+pub mod control;
 pub mod feed;
 pub mod host;
 pub mod http;
+pub mod interaction;
+pub mod metrics;
 pub mod notifier;
-pub mod ohlcv;
 pub mod provider;
+pub mod queue;
+pub mod replay;
 pub mod runner;
+pub mod scanner;
+pub mod schedule;
+pub mod security;
 pub mod storage;
 
 use std::env;
 
+use tokio::signal::unix::{signal, SignalKind};
+
+use self::control::Control;
 use self::http::Handler;
+use self::notifier::DryRun;
 use self::runner::{Config, Runner};
 
 #[tokio::main]
 async fn main() {
-    let c: Config = env::var("CONFIG")
-        .ok()
-        .and_then(|f| {
-            let f = std::fs::File::open(f).expect("opening config file");
-            serde_json::from_reader(f).expect("parsing config file")
-        })
-        .expect("config env not set");
+    let c = Config::load().expect("loading config");
 
     fast_log::init(
         fast_log::Config::new()
@@ -36,10 +42,61 @@ async fn main() {
     )
     .expect("initializing logger");
 
-    let runner = Runner::new(c).expect("configuring runner");
+    let dry_run = DryRun::from_args(env::args().skip(1));
+    if let Some(d) = &dry_run {
+        log::info!("dry run, writing alerts to {}", d);
+    }
+    let replay = replay::Mode::from_args(env::args().skip(1));
+    if let Some(m) = &replay {
+        log::info!("{} the feeds and hosts", m);
+    }
+    let runner = Runner::new(c, dry_run, replay)
+        .await
+        .expect("configuring runner");
     let storage = runner.storage.clone();
+    let control = runner.control.clone();
+    let analyzer = runner.analyzer();
     runner.spawn_cleanup();
-    tokio::task::spawn(runner.run());
+    let runner = tokio::task::spawn(runner.run());
     _ = &*self::http::AUTH_KEY;
-    Handler::serve(&env::var("ADDR").expect("address unset"), storage).await;
+    _ = &*self::interaction::PUBLIC_KEY;
+    tokio::task::spawn(shutdown_signal(control.clone()));
+    let (stop_server, server_stopped) = tokio::sync::oneshot::channel();
+    let addr = env::var("ADDR").expect("address unset");
+    let server = Handler::serve(&addr, storage, control, analyzer, async move {
+        _ = server_stopped.await;
+    });
+    tokio::pin!(server);
+
+    // don't keep serving the API if the runner is gone
+    tokio::select! {
+        res = runner => match res {
+            Ok(()) => log::info!("runner exited"),
+            Err(e) => {
+                log::error!("runner failed: {}", e);
+                log::logger().flush();
+                std::process::exit(1);
+            }
+        },
+        _ = &mut server => {
+            log::error!("server exited");
+            log::logger().flush();
+            std::process::exit(1);
+        }
+    }
+    // the runner has saved its state and drained the notifiers by now
+    _ = stop_server.send(());
+    server.await;
+    log::logger().flush();
+}
+
+/// Asks the runner to stop on SIGINT or SIGTERM.
+async fn shutdown_signal(control: Control) {
+    let mut term = signal(SignalKind::terminate()).expect("installing SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => (),
+        _ = term.recv() => (),
+    }
+    log::info!("received shutdown signal");
+    control.request_shutdown();
 }
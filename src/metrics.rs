@@ -0,0 +1,112 @@
+use prometheus::{
+    register_histogram_vec, register_int_counter, register_int_counter_vec, register_int_gauge,
+    register_int_gauge_vec, Encoder, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, TextEncoder,
+};
+
+lazy_static::lazy_static! {
+    pub static ref FEED_PAIRS: IntCounterVec = register_int_counter_vec!(
+        "scanner_feed_pairs_total",
+        "Pairs fetched from the feeds",
+        &["feed", "network"]
+    )
+    .unwrap();
+    pub static ref FEED_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "scanner_feed_errors_total",
+        "Failed feed requests",
+        &["feed", "network"]
+    )
+    .unwrap();
+    pub static ref FILTERED_PAIRS: IntCounterVec = register_int_counter_vec!(
+        "scanner_filtered_pairs_total",
+        "Pairs skipped before analysis",
+        &["reason", "network"]
+    )
+    .unwrap();
+    pub static ref HOST_INVOCATIONS: IntCounterVec = register_int_counter_vec!(
+        "scanner_host_invocations_total",
+        "Host function invocations",
+        &["region"]
+    )
+    .unwrap();
+    pub static ref HOST_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "scanner_host_failures_total",
+        "Failed host function invocations",
+        &["region"]
+    )
+    .unwrap();
+    pub static ref HOST_THROTTLED: IntCounterVec = register_int_counter_vec!(
+        "scanner_host_throttled_total",
+        "Requests rate limited by the upstream (by host region)",
+        &["region"]
+    )
+    .unwrap();
+    pub static ref WORKER_FETCH_DURATION: HistogramVec = register_histogram_vec!(
+        "scanner_worker_fetch_duration_seconds",
+        "Time taken by the worker for an upstream request, including retries (by host region)",
+        &["region"],
+        vec![0.1, 0.25, 0.5, 1., 2.5, 5., 10., 30.]
+    )
+    .unwrap();
+    pub static ref WORKER_FETCH_RETRIES: IntCounterVec = register_int_counter_vec!(
+        "scanner_worker_fetch_retries_total",
+        "Upstream requests retried by the worker (by host region)",
+        &["region"]
+    )
+    .unwrap();
+    pub static ref HOST_REQUEST_LIMIT: IntGaugeVec = register_int_gauge_vec!(
+        "scanner_host_request_limit",
+        "Requests sent to the host region per round",
+        &["region"]
+    )
+    .unwrap();
+    pub static ref PROVIDER_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "scanner_provider_errors_total",
+        "Failed OHLCV requests (by status code, if any)",
+        &["provider", "code"]
+    )
+    .unwrap();
+    pub static ref NOTIFIER_POSTS: IntCounterVec = register_int_counter_vec!(
+        "scanner_notifier_posts_total",
+        "Messages posted to the notifiers (by status code)",
+        &["code"]
+    )
+    .unwrap();
+    pub static ref NOTIFIER_RATE_LIMITS: IntCounter = register_int_counter!(
+        "scanner_notifier_rate_limits_total",
+        "Rate limited notifier posts"
+    )
+    .unwrap();
+    pub static ref BUFFER_SIZE: IntGauge = register_int_gauge!(
+        "scanner_buffer_size",
+        "Requests waiting to be dispatched"
+    )
+    .unwrap();
+    pub static ref SCAN_DURATION: HistogramVec = register_histogram_vec!(
+        "scanner_scan_duration_seconds",
+        "Time taken for scanning a network",
+        &["network"],
+        vec![60., 300., 900., 1800., 3600., 7200., 14400., 28800.]
+    )
+    .unwrap();
+    pub static ref SCAN_FAILURES: IntCounterVec = register_int_counter_vec!(
+        "scanner_scan_failures_total",
+        "Scans given up after repeatedly failing to fetch a page",
+        &["network"]
+    )
+    .unwrap();
+    pub static ref LAST_SUCCESS: IntGauge = register_int_gauge!(
+        "scanner_last_success_timestamp_seconds",
+        "Unix timestamp of the last completed run (across all networks)"
+    )
+    .unwrap();
+}
+
+/// Encodes all the registered metrics in the text format.
+pub fn gather() -> shared::Result<String> {
+    let mut buf = vec![];
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buf)
+        .map_err(|e| shared::Error::Runtime(e.to_string()))?;
+    Ok(String::from_utf8(buf)?)
+}
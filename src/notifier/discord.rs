@@ -1,6 +1,8 @@
 use async_std::sync::Mutex;
 use serde_json::json;
 
+use crate::metrics;
+
 use std::time::{Duration, Instant};
 
 const MAX_CHARS: usize = 1990;
@@ -33,11 +35,15 @@ impl BufferedDiscordWebhook {
 
 #[async_trait::async_trait]
 impl super::Notifier for BufferedDiscordWebhook {
+    async fn pending(&self) -> usize {
+        self.buffer.lock().await.msg.trim().len()
+    }
+
     async fn notify(&self, msg: &str) -> shared::Result<()> {
         // lock and block for the whole thing
         let mut g: async_std::sync::MutexGuard<RateLimitedBuffer> = self.buffer.lock().await;
         if !msg.trim().is_empty() {
-            g.msg.push_str(&msg);
+            g.msg.push_str(msg);
             g.msg.push('\n');
         }
         log::debug!("current buffer len: {}", g.msg.len());
@@ -84,7 +90,11 @@ impl super::Notifier for BufferedDiscordWebhook {
         // (as returned by X-RateLimit-Reset-After or Retry-After or 1 second if none helps)
         g.reset = reset.unwrap_or(Duration::from_secs(1));
         g.current = Instant::now();
+        metrics::NOTIFIER_POSTS
+            .with_label_values(&[res.status().as_str()])
+            .inc();
         if res.status().as_u16() == 429 {
+            metrics::NOTIFIER_RATE_LIMITS.inc();
             let reset: Option<Duration> = res
                 .headers()
                 .get("Retry-After")
@@ -105,7 +115,7 @@ impl super::Notifier for BufferedDiscordWebhook {
             Ok(())
         } else {
             Err(shared::Error::UnexpectedStatusCode(
-                res.status().as_u16().into(),
+                res.status().as_u16(),
                 Some(res.text().await?),
             ))
         }
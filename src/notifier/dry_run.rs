@@ -0,0 +1,144 @@
+use chrono::offset::Utc;
+use serde_json::json;
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use super::Notifier;
+
+/// Where the alerts go instead of the webhooks (for trying out config and detector changes).
+#[derive(Debug, Clone, PartialEq)]
+pub enum DryRun {
+    Stdout,
+    /// JSONL file (appended to)
+    File(String),
+}
+
+impl DryRun {
+    /// Parses `--dry-run` (stdout) or `--dry-run=<file>` from the command-line arguments.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        args.find_map(|a| match a.as_str() {
+            "--dry-run" => Some(DryRun::Stdout),
+            _ => a
+                .strip_prefix("--dry-run=")
+                .map(|path| DryRun::File(path.into())),
+        })
+    }
+
+    /// Returns the notifier for the given channel (network or watchlist).
+    pub fn notifier(&self, channel: &str) -> shared::Result<Arc<dyn Notifier + Send + Sync>> {
+        Ok(match self {
+            DryRun::Stdout => Arc::new(StdoutNotifier::new(channel)),
+            DryRun::File(path) => Arc::new(FileNotifier::new(path, channel)?),
+        })
+    }
+}
+
+impl std::fmt::Display for DryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DryRun::Stdout => write!(f, "stdout"),
+            DryRun::File(path) => write!(f, "{}", path),
+        }
+    }
+}
+
+/// Prints the alerts to stdout.
+pub struct StdoutNotifier {
+    channel: String,
+}
+
+impl StdoutNotifier {
+    pub fn new(channel: &str) -> Self {
+        StdoutNotifier {
+            channel: channel.into(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for StdoutNotifier {
+    async fn notify(&self, msg: &str) -> shared::Result<()> {
+        if !msg.trim().is_empty() {
+            println!("--- {} ---\n{}", self.channel, msg.trim());
+        }
+        Ok(())
+    }
+}
+
+/// Appends the alerts to a JSONL file (one object per alert).
+pub struct FileNotifier {
+    channel: String,
+    file: Mutex<File>,
+}
+
+impl FileNotifier {
+    pub fn new(path: &str, channel: &str) -> shared::Result<Self> {
+        Ok(FileNotifier {
+            channel: channel.into(),
+            file: Mutex::new(OpenOptions::new().create(true).append(true).open(path)?),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for FileNotifier {
+    async fn notify(&self, msg: &str) -> shared::Result<()> {
+        if msg.trim().is_empty() {
+            return Ok(());
+        }
+        let mut line = json!({
+            "timestamp": Utc::now(),
+            "channel": self.channel,
+            "message": msg.trim(),
+        })
+        .to_string();
+        line.push('\n');
+        // single write so that the lines from different channels don't interleave
+        self.file.lock().unwrap().write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DryRun;
+    use serde_json::Value;
+
+    #[test]
+    fn parse_args() {
+        let args = |a: &[&str]| DryRun::from_args(a.iter().map(|s| s.to_string()));
+        assert_eq!(args(&["scanner"]), None);
+        assert_eq!(args(&["scanner", "--dry-run"]), Some(DryRun::Stdout));
+        assert_eq!(
+            args(&["scanner", "--dry-run=alerts.jsonl"]),
+            Some(DryRun::File("alerts.jsonl".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn file_notifier_writes_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alerts.jsonl");
+        let dry_run = DryRun::File(path.to_string_lossy().into());
+        let solana = dry_run.notifier("solana").unwrap();
+        let watchlist = dry_run.notifier("watchlist").unwrap();
+        solana
+            .notify("### SOL/USDC\n`Range high 1.2 broken`\n")
+            .await
+            .unwrap();
+        solana.flush().await.unwrap();
+        watchlist.notify("### BONK/SOL").await.unwrap();
+
+        let lines = std::fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str::<Value>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["channel"], "solana");
+        assert_eq!(lines[0]["message"], "### SOL/USDC\n`Range high 1.2 broken`");
+        assert_eq!(lines[1]["channel"], "watchlist");
+    }
+}
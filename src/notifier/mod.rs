@@ -1,11 +1,13 @@
-use super::ohlcv::OHLCVList;
-use chrono::{offset::Utc, Datelike};
+use chrono::offset::Utc;
+use shared::ohlcv::Bars;
 
 use std::env;
 
 mod discord;
+mod dry_run;
 
 pub use self::discord::BufferedDiscordWebhook;
+pub use self::dry_run::{DryRun, FileNotifier, StdoutNotifier};
 
 lazy_static::lazy_static! {
     static ref THREE_DAY: bool = env::var("DAY3").is_ok();
@@ -15,7 +17,7 @@ lazy_static::lazy_static! {
 pub trait Notifier {
     async fn notify(&self, msg: &str) -> shared::Result<()>;
 
-    async fn post_analysis(&self, pair: &shared::Request, ohlcv: OHLCVList) -> shared::Result<()> {
+    async fn post_analysis(&self, pair: &shared::Request, bars: Bars) -> shared::Result<()> {
         let mut msg = String::new();
         msg.push_str("### ");
         msg.push_str(
@@ -40,50 +42,66 @@ pub trait Notifier {
         if pair.maybe_duplicate {
             msg.push_str(" (dup)");
         }
+        if pair.watched {
+            msg.push_str(" (watched)");
+        }
+        if !pair.sources.is_empty() {
+            msg.push_str(&format!(
+                " [{}]",
+                pair.sources
+                    .iter()
+                    .map(|(feed, rank)| format!("{} #{}", feed, rank))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
         msg.push_str(&format!("\n`{}`", pair.pool_address));
         if let Some(s) = get_links(&pair.network, &pair.pool_address) {
             msg.push(' ');
             msg.push_str(&s);
         }
+        if let Some(note) = &pair.note {
+            msg.push_str(&format!("\n> {}", note));
+        }
+        if !pair.risk_flags.is_empty() {
+            msg.push_str(&format!("\n`Risk: {}`", pair.risk_flags.join(", ")));
+        }
+        if !pair.siblings.is_empty() {
+            msg.push_str(&format!(
+                "\nalso: {}",
+                pair.siblings
+                    .iter()
+                    .map(|s| format!("`{}`", s))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
 
+        let signals = bars.signals(pair.provider, pair.timeframe);
         let mut yday_data = String::new();
-        let is_first_three_day_open = Utc::now().ordinal() % 3 == 1 || *THREE_DAY;
-        match ohlcv.clone().three_day().analyze() {
-            Some(analysis) if is_first_three_day_open => {
-                match analysis.bullish_engulfing.last() {
-                    Some(b) if b.idx == analysis.ohlcv.len() - 1 => {
-                        yday_data.push_str(&format!(
-                            "\n`3D Bullish engulfing ({} candles) at {}`",
-                            b.num_engulfing,
-                            analysis.ohlcv.last().unwrap().close,
-                        ));
-                    }
-                    _ => (),
-                }
-                match analysis.bearish_engulfing.last() {
-                    Some(b) if b.idx == analysis.ohlcv.len() - 1 => {
-                        yday_data.push_str(&format!(
-                            "\n`3D Bearish engulfing ({} candles) at {}`",
-                            b.num_engulfing,
-                            analysis.ohlcv.last().unwrap().close,
-                        ));
-                    }
-                    _ => (),
-                }
+        let is_first_three_day_open = pair.timeframe.starts_group(Utc::now()) || *THREE_DAY;
+        if is_first_three_day_open {
+            let close = signals.close_3d.unwrap_or_default();
+            let label = pair.timeframe.grouped_name();
+            if let Some(b) = signals.bullish_engulfing_3d {
+                yday_data.push_str(&format!(
+                    "\n`{} Bullish engulfing ({} candles) at {}`",
+                    label, b.num_engulfing, close,
+                ));
             }
-            _ => (),
-        }
-
-        if let Some(analysis) = ohlcv.analyze() {
-            if let Some(yday) = analysis.last_day_data() {
-                if let Some(b) = yday.range_high_break {
-                    yday_data.push_str(&format!("\n`Range high {} broken`", b.prev_bound));
-                }
-                if let Some(b) = yday.range_low_break {
-                    yday_data.push_str(&format!("\n`Range low {} broken`", b.prev_bound));
-                }
+            if let Some(b) = signals.bearish_engulfing_3d {
+                yday_data.push_str(&format!(
+                    "\n`{} Bearish engulfing ({} candles) at {}`",
+                    label, b.num_engulfing, close,
+                ));
             }
         }
+        if let Some(b) = signals.range_high_break {
+            yday_data.push_str(&format!("\n`Range high {} broken`", b.prev_bound));
+        }
+        if let Some(b) = signals.range_low_break {
+            yday_data.push_str(&format!("\n`Range low {} broken`", b.prev_bound));
+        }
 
         if yday_data.is_empty() {
             log::warn!(
@@ -103,6 +121,11 @@ pub trait Notifier {
     async fn flush(&self) -> shared::Result<()> {
         self.notify("").await
     }
+
+    /// Length of the buffered messages that are yet to be posted.
+    async fn pending(&self) -> usize {
+        0
+    }
 }
 
 fn get_links(network: &str, pool_address: &str) -> Option<String> {
@@ -1,9 +1,69 @@
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
 
-mod gt;
+mod rpc;
 
 pub trait Provider: DeserializeOwned {
-    fn ohlcv_data(&self) -> shared::Result<crate::ohlcv::OHLCVList>;
+    fn ohlcv_data(&self) -> shared::Result<shared::ohlcv::OHLCVList>;
+
+    /// Parses the response body returned by the given provider.
+    fn parse(_provider: shared::ProviderId, body: &str) -> shared::Result<Self> {
+        serde_json::from_str(body).map_err(shared::Error::Serde)
+    }
+}
+
+pub use self::rpc::{Config as RpcConfig, RpcSwaps};
+
+pub use shared::{Birdeye, CoinGecko, GeckoTerminal};
+
+// the response bodies are parsed in `shared` (so that the workers can parse them as well)
+impl Provider for GeckoTerminal {
+    fn ohlcv_data(&self) -> shared::Result<shared::ohlcv::OHLCVList> {
+        self.candles()?.try_into()
+    }
+}
+
+impl Provider for Birdeye {
+    fn ohlcv_data(&self) -> shared::Result<shared::ohlcv::OHLCVList> {
+        self.candles()?.try_into()
+    }
 }
 
-pub use self::gt::GeckoTerminal;
+impl Provider for CoinGecko {
+    fn ohlcv_data(&self) -> shared::Result<shared::ohlcv::OHLCVList> {
+        self.candles()?.try_into()
+    }
+}
+
+/// Dispatches responses to the parser of the provider that served them.
+#[derive(Deserialize, Debug)]
+pub enum MultiProvider {
+    GeckoTerminal(GeckoTerminal),
+    Birdeye(Birdeye),
+    CoinGecko(CoinGecko),
+}
+
+impl Provider for MultiProvider {
+    fn ohlcv_data(&self) -> shared::Result<shared::ohlcv::OHLCVList> {
+        match self {
+            MultiProvider::GeckoTerminal(p) => p.ohlcv_data(),
+            MultiProvider::Birdeye(p) => p.ohlcv_data(),
+            MultiProvider::CoinGecko(p) => p.ohlcv_data(),
+        }
+    }
+
+    fn parse(provider: shared::ProviderId, body: &str) -> shared::Result<Self> {
+        Ok(match provider {
+            shared::ProviderId::GeckoTerminal => {
+                MultiProvider::GeckoTerminal(serde_json::from_str(body)?)
+            }
+            shared::ProviderId::Birdeye => MultiProvider::Birdeye(serde_json::from_str(body)?),
+            shared::ProviderId::CoinGecko => MultiProvider::CoinGecko(serde_json::from_str(body)?),
+            shared::ProviderId::Rpc => {
+                return Err(shared::Error::Config(
+                    "RPC candles aren't fetched through hosts".into(),
+                ))
+            }
+        })
+    }
+}
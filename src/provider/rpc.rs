@@ -0,0 +1,689 @@
+use chrono::{offset::Utc, DateTime};
+use futures::{stream, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+
+use shared::ohlcv::{OHLCVList, OHLCV};
+
+// keccak256("Swap(address,uint256,uint256,uint256,uint256,address)")
+const UNISWAP_V2_SWAP: &str = "0xd78ad95fa46c994b6551d0da85fc275fe613ce37657fb8d5e3d130840159d822";
+// keccak256("Swap(address,address,int256,int256,uint160,uint128,int24)")
+const UNISWAP_V3_SWAP: &str = "0xc42079f94a6350d7e6235f29174924f928cc2ac818eb64fed8004e115fbcca67";
+const TOKEN0: &str = "0x0dfe1681";
+const TOKEN1: &str = "0xd21220a7";
+const DECIMALS: &str = "0x313ce567";
+const WRAPPED_SOL: &str = "So11111111111111111111111111111111111111112";
+const SIGNATURES_LIMIT: usize = 1000;
+const CONCURRENT_REQUESTS: usize = 8;
+
+const fn default_days() -> i64 {
+    30
+}
+const fn default_block_range() -> u64 {
+    2000
+}
+const fn default_max_transactions() -> usize {
+    2000
+}
+const fn default_concurrent_pools() -> usize {
+    4
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// JSON-RPC endpoints keyed by network
+    pub endpoints: HashMap<String, String>,
+    #[serde(default = "default_days")]
+    pub days: i64,
+    /// Max blocks per `eth_getLogs` request
+    #[serde(default = "default_block_range")]
+    pub block_range: u64,
+    /// Max (Solana) transactions fetched per pool
+    #[serde(default = "default_max_transactions")]
+    pub max_transactions: usize,
+    /// Max pools whose candles are built at a time (the others wait for the next flush)
+    #[serde(default = "default_concurrent_pools")]
+    pub concurrent_pools: usize,
+}
+
+#[async_trait::async_trait]
+pub trait Transport {
+    async fn call(&self, url: &str, method: &str, params: Value) -> shared::Result<Value>;
+}
+
+#[derive(Default)]
+pub struct Http(reqwest::Client);
+
+#[async_trait::async_trait]
+impl Transport for Http {
+    async fn call(&self, url: &str, method: &str, params: Value) -> shared::Result<Value> {
+        log::debug!("POST {} ({})", url, method);
+        let resp = self
+            .0
+            .post(url)
+            .json(&json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await?;
+        let code = resp.status();
+        let bytes = resp.bytes().await?;
+        if !code.is_success() {
+            return Err(shared::Error::UnexpectedStatusCode(
+                code.as_u16(),
+                Some(String::from_utf8_lossy(&bytes).to_string()),
+            ));
+        }
+        let mut v: Value = serde_json::from_slice(&bytes)?;
+        if let Some(e) = v.get("error") {
+            return Err(shared::Error::UnexpectedResponse(e.to_string()));
+        }
+        Ok(v["result"].take())
+    }
+}
+
+/// Swap reduced to the price of the base token (in quote token) and the volume in quote token.
+#[derive(Debug, Clone, Copy)]
+struct Swap {
+    timestamp: i64,
+    price: f64,
+    volume: f64,
+}
+
+/// Reconstructs daily candles from on-chain swaps for pools that the
+/// other providers don't index (or rate-limit). Prices and volumes are
+/// denominated in the quote token rather than USD.
+pub struct RpcSwaps<T = Http> {
+    transport: T,
+    config: Config,
+}
+
+impl RpcSwaps<Http> {
+    pub fn new(config: Config) -> shared::Result<Self> {
+        for (key, value) in [
+            ("block_range", config.block_range as usize),
+            ("max_transactions", config.max_transactions),
+            ("concurrent_pools", config.concurrent_pools),
+        ] {
+            if value == 0 {
+                return Err(shared::Error::Config(format!(
+                    "rpc.{} must be positive",
+                    key
+                )));
+            }
+        }
+        Ok(RpcSwaps {
+            transport: Http::default(),
+            config,
+        })
+    }
+}
+
+impl<T> RpcSwaps<T>
+where
+    T: Transport + Send + Sync,
+{
+    pub fn supports(&self, network: &str) -> bool {
+        network != "ton" && self.config.endpoints.contains_key(network)
+    }
+
+    pub fn concurrent_pools(&self) -> usize {
+        self.config.concurrent_pools
+    }
+
+    pub async fn ohlcv_data(&self, req: &shared::Request) -> shared::Result<OHLCVList> {
+        let url = self.config.endpoints.get(&req.network).ok_or_else(|| {
+            shared::Error::Config(format!("no RPC endpoint for network {}", req.network))
+        })?;
+        let cutoff = Utc::now().timestamp() - self.config.days * 86400;
+        let mut swaps = if req.network == "solana" {
+            self.solana_swaps(url, req, cutoff).await?
+        } else {
+            self.evm_swaps(url, req, cutoff).await?
+        };
+        swaps.sort_by_key(|s| s.timestamp);
+        candles(swaps)
+    }
+
+    async fn eth_call(&self, url: &str, to: &str, data: &str) -> shared::Result<String> {
+        let v = self
+            .transport
+            .call(
+                url,
+                "eth_call",
+                json!([{ "to": to, "data": data }, "latest"]),
+            )
+            .await?;
+        v.as_str()
+            .map(|s| s.trim_start_matches("0x").to_string())
+            .ok_or_else(|| shared::Error::UnexpectedResponse(v.to_string()))
+    }
+
+    async fn evm_swaps(
+        &self,
+        url: &str,
+        req: &shared::Request,
+        cutoff: i64,
+    ) -> shared::Result<Vec<Swap>> {
+        let pool = req.pool_address.as_str();
+        let token0 = format!("0x{}", address(&self.eth_call(url, pool, TOKEN0).await?));
+        let token1 = format!("0x{}", address(&self.eth_call(url, pool, TOKEN1).await?));
+        let decimals0 = word(&self.eth_call(url, &token0, DECIMALS).await?, false) as i32;
+        let decimals1 = word(&self.eth_call(url, &token1, DECIMALS).await?, false) as i32;
+        // prices are of token0 in token1 unless the base token is token1
+        let inverted = req.base_token_address.as_deref() == Some(token1.as_str());
+
+        let latest = hex_u64(
+            &self
+                .transport
+                .call(url, "eth_blockNumber", json!([]))
+                .await?,
+        )?;
+        let first = self.first_block_since(url, latest, cutoff).await?;
+        let range = self.config.block_range;
+        let logs = stream::iter((first..=latest).step_by(range as usize).map(|from| {
+            self.transport.call(
+                url,
+                "eth_getLogs",
+                json!([{
+                    "address": pool,
+                    "fromBlock": format!("{:#x}", from),
+                    "toBlock": format!("{:#x}", (from + range - 1).min(latest)),
+                    "topics": [[UNISWAP_V2_SWAP, UNISWAP_V3_SWAP]],
+                }]),
+            )
+        }))
+        .buffered(CONCURRENT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<shared::Result<Vec<_>>>()?;
+        let logs = logs
+            .iter()
+            .flat_map(|l| l.as_array().into_iter().flatten())
+            .collect::<Vec<_>>();
+
+        // not all nodes include the block timestamps in the logs
+        let mut missing = vec![];
+        for log in &logs {
+            if log.get("blockTimestamp").is_none_or(Value::is_null) {
+                missing.push(hex_u64(&log["blockNumber"])?);
+            }
+        }
+        missing.sort_unstable();
+        missing.dedup();
+        let timestamps = stream::iter(missing.into_iter().map(|block| async move {
+            Ok::<_, shared::Error>((block, self.block_timestamp(url, block).await?))
+        }))
+        .buffered(CONCURRENT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<shared::Result<HashMap<_, _>>>()?;
+
+        let mut swaps = vec![];
+        for log in logs {
+            let timestamp = match log.get("blockTimestamp") {
+                Some(t) if !t.is_null() => hex_u64(t)? as i64,
+                _ => timestamps[&hex_u64(&log["blockNumber"])?],
+            };
+            if timestamp < cutoff {
+                continue;
+            }
+            let data = log["data"].as_str().unwrap_or_default();
+            let data = data.trim_start_matches("0x");
+            let (amount0, amount1, price) = match log["topics"][0].as_str() {
+                Some(UNISWAP_V2_SWAP) if data.len() >= 256 => {
+                    let amount0 = word(&data[0..64], false) - word(&data[128..192], false);
+                    let amount1 = word(&data[64..128], false) - word(&data[192..256], false);
+                    (amount0, amount1, None)
+                }
+                Some(UNISWAP_V3_SWAP) if data.len() >= 192 => {
+                    let sqrt_price = word(&data[128..192], false) / 2f64.powi(96);
+                    (
+                        word(&data[0..64], true),
+                        word(&data[64..128], true),
+                        Some(sqrt_price * sqrt_price),
+                    )
+                }
+                _ => continue,
+            };
+            let amount0 = amount0.abs() / 10f64.powi(decimals0);
+            let amount1 = amount1.abs() / 10f64.powi(decimals1);
+            let price = match price {
+                Some(p) => p * 10f64.powi(decimals0 - decimals1),
+                None if amount0 > 0. => amount1 / amount0,
+                None => continue,
+            };
+            if price == 0. || !price.is_finite() {
+                continue;
+            }
+            swaps.push(if inverted {
+                Swap {
+                    timestamp,
+                    price: 1. / price,
+                    volume: amount0,
+                }
+            } else {
+                Swap {
+                    timestamp,
+                    price,
+                    volume: amount1,
+                }
+            });
+        }
+        Ok(swaps)
+    }
+
+    /// Finds the first block mined at or after the timestamp (by bisecting the blocks).
+    async fn first_block_since(
+        &self,
+        url: &str,
+        latest: u64,
+        timestamp: i64,
+    ) -> shared::Result<u64> {
+        let (mut lo, mut hi) = (0, latest);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if self.block_timestamp(url, mid).await? < timestamp {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(lo)
+    }
+
+    async fn block_timestamp(&self, url: &str, block: u64) -> shared::Result<i64> {
+        let v = self
+            .transport
+            .call(
+                url,
+                "eth_getBlockByNumber",
+                json!([format!("{:#x}", block), false]),
+            )
+            .await?;
+        Ok(hex_u64(&v["timestamp"])? as i64)
+    }
+
+    async fn solana_swaps(
+        &self,
+        url: &str,
+        req: &shared::Request,
+        cutoff: i64,
+    ) -> shared::Result<Vec<Swap>> {
+        let base = req.base_token_address.as_deref().ok_or_else(|| {
+            shared::Error::Config(format!("missing base token for {}", req.pool_address))
+        })?;
+        let quote = req.quote_token_address.as_deref().ok_or_else(|| {
+            shared::Error::Config(format!("missing quote token for {}", req.pool_address))
+        })?;
+
+        let mut signatures = vec![];
+        let mut before: Option<String> = None;
+        // block time of the last transaction taken if there are more than `max_transactions`
+        let mut truncated = None;
+        'outer: loop {
+            let mut options = json!({ "limit": SIGNATURES_LIMIT });
+            if let Some(b) = &before {
+                options["before"] = json!(b);
+            }
+            let v = self
+                .transport
+                .call(
+                    url,
+                    "getSignaturesForAddress",
+                    json!([req.pool_address, options]),
+                )
+                .await?;
+            let page = v.as_array().cloned().unwrap_or_default();
+            for s in &page {
+                let time = s["blockTime"].as_i64().unwrap_or_default();
+                if time < cutoff {
+                    break 'outer;
+                }
+                if !s["err"].is_null() {
+                    continue;
+                }
+                if let Some(sig) = s["signature"].as_str() {
+                    signatures.push(sig.to_string());
+                }
+                if signatures.len() >= self.config.max_transactions {
+                    truncated = Some(time);
+                    break 'outer;
+                }
+            }
+            if page.len() < SIGNATURES_LIMIT {
+                break;
+            }
+            before = page
+                .last()
+                .and_then(|s| s["signature"].as_str())
+                .map(Into::into);
+        }
+
+        let txs = stream::iter(signatures.into_iter().map(|sig| {
+            self.transport.call(
+                url,
+                "getTransaction",
+                json!([sig, { "encoding": "jsonParsed", "maxSupportedTransactionVersion": 0 }]),
+            )
+        }))
+        .buffered(CONCURRENT_REQUESTS)
+        .collect::<Vec<_>>()
+        .await;
+
+        let mut swaps = vec![];
+        for tx in txs {
+            if let Some(swap) = solana_swap(&tx?, base, quote) {
+                swaps.push(swap);
+            }
+        }
+        if let Some(t) = truncated {
+            // the day of the last transaction taken is incomplete
+            let start = t.div_euclid(86400) * 86400 + 86400;
+            log::warn!(
+                "pool {} has over {} transactions in the last {} days, only using the swaps since {}",
+                req.pool_address,
+                self.config.max_transactions,
+                self.config.days,
+                DateTime::from_timestamp(start, 0).unwrap_or_default()
+            );
+            swaps.retain(|s| s.timestamp >= start);
+        }
+        Ok(swaps)
+    }
+}
+
+/// Derives the swap from the token balance changes of the transaction signer,
+/// skipping the transactions that don't settle in the quote token.
+fn solana_swap(tx: &Value, base: &str, quote: &str) -> Option<Swap> {
+    let timestamp = tx["blockTime"].as_i64()?;
+    let meta = &tx["meta"];
+    let signer = tx["transaction"]["message"]["accountKeys"][0]["pubkey"].as_str()?;
+
+    let mut deltas: HashMap<&str, f64> = HashMap::new();
+    for (key, sign) in [("preTokenBalances", -1.), ("postTokenBalances", 1.)] {
+        for b in meta[key].as_array().into_iter().flatten() {
+            if b["owner"].as_str() != Some(signer) {
+                continue;
+            }
+            let amount = b["uiTokenAmount"]["uiAmountString"]
+                .as_str()
+                .and_then(|s| s.parse::<f64>().ok())
+                .unwrap_or_default();
+            *deltas.entry(b["mint"].as_str()?).or_default() += sign * amount;
+        }
+    }
+
+    let base_delta = deltas.get(base).copied().unwrap_or_default();
+    if base_delta == 0. {
+        return None;
+    }
+    let quote_delta = match deltas.get(quote) {
+        Some(&d) if d != 0. => d,
+        // wrapped SOL is usually (un)wrapped within the swap, settling in native SOL
+        _ if quote == WRAPPED_SOL => {
+            let pre = meta["preBalances"][0].as_f64()?;
+            let post = meta["postBalances"][0].as_f64()?;
+            let fee = meta["fee"].as_f64().unwrap_or_default();
+            (post - pre + fee) / 1e9
+        }
+        _ => return None,
+    };
+    if quote_delta == 0. || quote_delta.signum() == base_delta.signum() {
+        return None;
+    }
+    Some(Swap {
+        timestamp,
+        price: (quote_delta / base_delta).abs(),
+        volume: quote_delta.abs(),
+    })
+}
+
+fn candles(swaps: Vec<Swap>) -> shared::Result<OHLCVList> {
+    let mut vec: Vec<OHLCV> = vec![];
+    for s in swaps {
+        let day = s.timestamp.div_euclid(86400) * 86400;
+        match vec.last_mut() {
+            Some(c) if c.timestamp.timestamp() == day => {
+                c.high = c.high.max(s.price);
+                c.low = c.low.min(s.price);
+                c.close = s.price;
+                c.volume += s.volume;
+                continue;
+            }
+            _ => (),
+        }
+        vec.push(OHLCV {
+            timestamp: DateTime::from_timestamp(day, 0)
+                .ok_or(shared::Error::InvalidTimestamp(day))?,
+            open: s.price,
+            high: s.price,
+            low: s.price,
+            close: s.price,
+            volume: s.volume,
+        });
+    }
+    Ok(vec.into())
+}
+
+fn hex_u64(v: &Value) -> shared::Result<u64> {
+    v.as_str()
+        .and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+        .ok_or_else(|| shared::Error::UnexpectedResponse(v.to_string()))
+}
+
+/// Last 20 bytes of an ABI-encoded word.
+fn address(word: &str) -> &str {
+    &word[word.len().saturating_sub(40)..]
+}
+
+/// Approximates an ABI-encoded (u)int256 word as a float.
+fn word(word: &str, signed: bool) -> f64 {
+    let digits = word.chars().filter_map(|c| c.to_digit(16));
+    let negative = signed && word.chars().next().and_then(|c| c.to_digit(16)) >= Some(8);
+    if negative {
+        // two's complement
+        -(digits.fold(0f64, |acc, d| acc * 16. + (15 - d) as f64) + 1.)
+    } else {
+        digits.fold(0f64, |acc, d| acc * 16. + d as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, RpcSwaps, Transport};
+    use chrono::offset::Utc;
+    use serde_json::{json, Value};
+
+    use std::collections::HashMap;
+
+    /// Returns the response for the method and params.
+    type Handler = Box<dyn Fn(&str, &Value) -> Value + Send + Sync>;
+
+    struct Fixture(Handler);
+
+    #[async_trait::async_trait]
+    impl Transport for Fixture {
+        async fn call(&self, _url: &str, method: &str, params: Value) -> shared::Result<Value> {
+            Ok((self.0)(method, &params))
+        }
+    }
+
+    fn rpc(
+        network: &str,
+        f: impl Fn(&str, &Value) -> Value + Send + Sync + 'static,
+    ) -> RpcSwaps<Fixture> {
+        RpcSwaps {
+            transport: Fixture(Box::new(f)),
+            config: Config {
+                endpoints: HashMap::from([(network.to_string(), "http://localhost".to_string())]),
+                days: 30,
+                block_range: 2000,
+                max_transactions: 100,
+                concurrent_pools: 4,
+            },
+        }
+    }
+
+    fn request(network: &str, pool: &str, base: &str, quote: &str) -> shared::Request {
+        shared::Request {
+            network: network.into(),
+            pool_address: pool.into(),
+            provider: shared::ProviderId::Rpc,
+            base_token_address: Some(base.into()),
+            quote_token_address: Some(quote.into()),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn evm_candles() {
+        let now = Utc::now().timestamp();
+        let midnight = now.div_euclid(86400) * 86400;
+        let (day1, day2) = (midnight - 2 * 86400, midnight - 86400);
+        let logs = json!([{
+            "blockNumber": "0x50",
+            "blockTimestamp": format!("{:#x}", day1 + 3600),
+            "topics": [super::UNISWAP_V2_SWAP],
+            "data": "0x000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000001e84800000000000000000000000000000000000000000000000000de0b6b3a76400000000000000000000000000000000000000000000000000000000000000000000",
+        }, {
+            // timestamp is looked up separately
+            "blockNumber": "0x52",
+            "topics": [super::UNISWAP_V2_SWAP],
+            "data": "0x00000000000000000000000000000000000000000000000006f05b59d3b2000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000016e360",
+        }, {
+            "blockNumber": "0x60",
+            "blockTimestamp": format!("{:#x}", day2 + 3600),
+            "topics": [super::UNISWAP_V3_SWAP],
+            "data": "0xfffffffffffffffffffffffffffffffffffffffffffffffff21f494c589c000000000000000000000000000000000000000000000000000000000000003d090000000000000000000000000000000000000000000000218def416bdb1a0000000000000000000000000000000000000000000000000000056bc75e2d63100000fffffffffffffffffffffffffffffffffffffffffffffffffffffffffffcf2c0",
+        }]);
+        let block = |v: &Value| u64::from_str_radix(&v.as_str().unwrap()[2..], 16).unwrap();
+        let mut rpc = rpc("eth", move |method, params| match method {
+            "eth_blockNumber" => json!("0x64"),
+            // a block a day, so the last 30 days start at block 70
+            "eth_getBlockByNumber" => match block(&params[0]) {
+                0x52 => json!({ "timestamp": format!("{:#x}", day1 + 7200) }),
+                n => json!({ "timestamp": format!("{:#x}", now - (100 - n as i64) * 86400) }),
+            },
+            "eth_getLogs" => {
+                let (from, to) = (block(&params[0]["fromBlock"]), block(&params[0]["toBlock"]));
+                assert!(from >= 70 && to <= 100 && to - from < 10);
+                let logs = logs.as_array().unwrap().iter();
+                json!(logs
+                    .filter(|l| (from..=to).contains(&block(&l["blockNumber"])))
+                    .collect::<Vec<_>>())
+            }
+            "eth_call" => match (params[0]["to"].as_str(), params[0]["data"].as_str()) {
+                (_, Some(super::TOKEN0)) => json!(format!("0x{:0>64}", "aa".repeat(20))),
+                (_, Some(super::TOKEN1)) => json!(format!("0x{:0>64}", "bb".repeat(20))),
+                (Some(t), _) if t.ends_with("aa") => json!(format!("0x{:064x}", 18)),
+                _ => json!(format!("0x{:064x}", 6)),
+            },
+            _ => unreachable!("unexpected method {}", method),
+        });
+        rpc.config.block_range = 10;
+
+        let (token0, token1) = (
+            format!("0x{}", "aa".repeat(20)),
+            format!("0x{}", "bb".repeat(20)),
+        );
+        let req = request("eth", "0xpool", &token0, &token1);
+        let ohlcv = rpc.ohlcv_data(&req).await.expect("candles");
+        assert_eq!(ohlcv.len(), 2);
+        assert_eq!(ohlcv[0].timestamp.timestamp(), day1);
+        assert_eq!(ohlcv[0].open, 2.);
+        assert_eq!(ohlcv[0].high, 3.);
+        assert_eq!(ohlcv[0].low, 2.);
+        assert_eq!(ohlcv[0].close, 3.);
+        assert_eq!(ohlcv[0].volume, 3.5);
+        assert_eq!(ohlcv[1].timestamp.timestamp(), day2);
+        assert!((ohlcv[1].close - 4.).abs() < 1e-6);
+        assert_eq!(ohlcv[1].volume, 4.);
+
+        // prices are inverted when the base token is token1
+        let req = request("eth", "0xpool", &token1, &token0);
+        let ohlcv = rpc.ohlcv_data(&req).await.expect("candles");
+        assert_eq!(ohlcv[0].open, 0.5);
+        assert_eq!(ohlcv[0].volume, 1.5);
+    }
+
+    #[tokio::test]
+    async fn solana_candles() {
+        let day = Utc::now().timestamp().div_euclid(86400) * 86400 - 86400;
+        let base = "C1XRDFrD9EYNUvi69SD9tZs4nvq3oVWNACUfFHpwWu3y";
+        let usdc = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+        let fixture = move |method: &str, params: &Value| match method {
+            "getSignaturesForAddress" => json!([
+                { "signature": "sig2", "blockTime": day + 7200, "err": null },
+                { "signature": "failed", "blockTime": day + 5400, "err": { "InstructionError": [0, "Custom"] } },
+                { "signature": "sig1", "blockTime": day + 3600, "err": null },
+            ]),
+            "getTransaction" => match params[0].as_str() {
+                // buys 100 tokens for 1 SOL
+                Some("sig1") => json!({
+                    "blockTime": day + 3600,
+                    "transaction": { "message": { "accountKeys": [{ "pubkey": "trader" }] } },
+                    "meta": {
+                        "fee": 5000,
+                        "preBalances": [3_000_005_000u64],
+                        "postBalances": [2_000_000_000u64],
+                        "preTokenBalances": [],
+                        "postTokenBalances": [
+                            { "mint": base, "owner": "trader", "uiTokenAmount": { "uiAmountString": "100" } },
+                        ],
+                    },
+                }),
+                // sells 50 tokens for 2 USDC
+                _ => json!({
+                    "blockTime": day + 7200,
+                    "transaction": { "message": { "accountKeys": [{ "pubkey": "trader" }] } },
+                    "meta": {
+                        "fee": 5000,
+                        "preBalances": [2_000_000_000u64],
+                        "postBalances": [1_999_995_000u64],
+                        "preTokenBalances": [
+                            { "mint": base, "owner": "trader", "uiTokenAmount": { "uiAmountString": "100" } },
+                            { "mint": usdc, "owner": "trader", "uiTokenAmount": { "uiAmountString": "0" } },
+                            { "mint": base, "owner": "pool", "uiTokenAmount": { "uiAmountString": "1000" } },
+                        ],
+                        "postTokenBalances": [
+                            { "mint": base, "owner": "trader", "uiTokenAmount": { "uiAmountString": "50" } },
+                            { "mint": usdc, "owner": "trader", "uiTokenAmount": { "uiAmountString": "2" } },
+                            { "mint": base, "owner": "pool", "uiTokenAmount": { "uiAmountString": "1050" } },
+                        ],
+                    },
+                }),
+            },
+            _ => unreachable!("unexpected method {}", method),
+        };
+
+        // swaps settling in other tokens are left out
+        let req = request("solana", "pool", base, super::WRAPPED_SOL);
+        let ohlcv = rpc("solana", fixture)
+            .ohlcv_data(&req)
+            .await
+            .expect("candles");
+        assert_eq!(ohlcv.len(), 1);
+        assert_eq!(ohlcv[0].timestamp.timestamp(), day);
+        assert_eq!(ohlcv[0].open, 0.01);
+        assert_eq!(ohlcv[0].close, 0.01);
+        assert_eq!(ohlcv[0].volume, 1.);
+
+        let req = request("solana", "pool", base, usdc);
+        let ohlcv = rpc("solana", fixture)
+            .ohlcv_data(&req)
+            .await
+            .expect("candles");
+        assert_eq!(ohlcv.len(), 1);
+        assert_eq!(ohlcv[0].open, 0.04);
+        assert_eq!(ohlcv[0].close, 0.04);
+        assert_eq!(ohlcv[0].volume, 2.);
+
+        // the day of the last transaction taken is left out when they're capped
+        let mut capped = rpc("solana", fixture);
+        capped.config.max_transactions = 1;
+        assert!(capped.ohlcv_data(&req).await.expect("candles").is_empty());
+    }
+}
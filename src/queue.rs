@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+/// Requests waiting to be dispatched, queued per network so that a network with
+/// many pages doesn't hog the host invocations.
+#[derive(Default)]
+pub struct Queue {
+    networks: Vec<(String, VecDeque<shared::Request>)>,
+    /// Network to take from first in the next batch
+    next: usize,
+}
+
+impl Queue {
+    pub fn len(&self) -> usize {
+        self.networks.iter().map(|(_, q)| q.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.iter().all(|(_, q)| q.is_empty())
+    }
+
+    pub fn push(&mut self, req: shared::Request) {
+        match self.networks.iter_mut().find(|(n, _)| *n == req.network) {
+            Some((_, q)) => q.push_back(req),
+            None => self
+                .networks
+                .push((req.network.clone(), VecDeque::from([req]))),
+        }
+    }
+
+    pub fn extend(&mut self, requests: impl IntoIterator<Item = shared::Request>) {
+        for req in requests {
+            self.push(req);
+        }
+    }
+
+    /// Takes up to `n` requests, with the networks taking turns.
+    pub fn take(&mut self, n: usize) -> Vec<shared::Request> {
+        let mut taken = Vec::with_capacity(n.min(self.len()));
+        while taken.len() < n && !self.is_empty() {
+            let idx = self.next % self.networks.len();
+            self.next = idx + 1;
+            if let Some(req) = self.networks[idx].1.pop_front() {
+                taken.push(req);
+            }
+        }
+        taken
+    }
+
+    /// Removes (and returns) the requests matching the predicate.
+    pub fn extract(&mut self, f: impl Fn(&shared::Request) -> bool) -> Vec<shared::Request> {
+        let mut extracted = vec![];
+        for (_, q) in &mut self.networks {
+            let (matched, rest): (Vec<_>, Vec<_>) = q.drain(..).partition(&f);
+            extracted.extend(matched);
+            *q = rest.into();
+        }
+        extracted
+    }
+
+    pub fn find_mut(
+        &mut self,
+        network: &str,
+        f: impl Fn(&shared::Request) -> bool,
+    ) -> Option<&mut shared::Request> {
+        self.networks
+            .iter_mut()
+            .find(|(n, _)| n == network)
+            .and_then(|(_, q)| q.iter_mut().find(|r| f(r)))
+    }
+
+    pub fn drain(&mut self) -> Vec<shared::Request> {
+        self.networks
+            .drain(..)
+            .flat_map(|(_, q)| q.into_iter())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Queue;
+
+    fn request(network: &str, pool: &str) -> shared::Request {
+        shared::Request {
+            network: network.into(),
+            pool_address: pool.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn networks_take_turns() {
+        let mut queue = Queue::default();
+        queue.extend((0..5).map(|i| request("solana", &format!("s{}", i))));
+        queue.extend((0..2).map(|i| request("base", &format!("b{}", i))));
+        assert_eq!(queue.len(), 7);
+
+        let pools = |reqs: Vec<shared::Request>| {
+            reqs.into_iter().map(|r| r.pool_address).collect::<Vec<_>>()
+        };
+        assert_eq!(pools(queue.take(4)), ["s0", "b0", "s1", "b1"]);
+        queue.push(request("base", "b2"));
+        assert_eq!(pools(queue.take(2)), ["s2", "b2"]);
+        assert_eq!(pools(queue.take(10)), ["s3", "s4"]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn extract_and_find() {
+        let mut queue = Queue::default();
+        queue.extend([
+            request("solana", "s0"),
+            request("base", "b0"),
+            request("solana", "s1"),
+        ]);
+        assert!(queue.find_mut("base", |r| r.pool_address == "s0").is_none());
+        queue
+            .find_mut("solana", |r| r.pool_address == "s1")
+            .unwrap()
+            .watched = true;
+
+        let watched = queue.extract(|r| r.watched);
+        assert_eq!(watched.len(), 1);
+        assert_eq!(watched[0].pool_address, "s1");
+        assert_eq!(queue.drain().len(), 2);
+        assert!(queue.is_empty());
+    }
+}
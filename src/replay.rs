@@ -0,0 +1,610 @@
+use async_std::sync::Mutex as AsyncMutex;
+use serde::{Deserialize, Serialize};
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+
+use super::{
+    feed::{FeedClient, Network, Pair, Source},
+    host::Host,
+    provider::Provider,
+};
+
+/// Recording or replaying the feeds and hosts (for reproducing a cycle offline).
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mode {
+    /// JSONL file to append the feed pages and worker responses to
+    Record(String),
+    /// JSONL file to serve the feed pages and worker responses from
+    Replay(String),
+}
+
+impl Mode {
+    /// Parses `--record=<file>` or `--replay=<file>` from the command-line arguments.
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Option<Self> {
+        args.find_map(|a| {
+            if let Some(path) = a.strip_prefix("--record=") {
+                Some(Mode::Record(path.into()))
+            } else {
+                a.strip_prefix("--replay=")
+                    .map(|path| Mode::Replay(path.into()))
+            }
+        })
+    }
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Record(path) => write!(f, "recording to {}", path),
+            Mode::Replay(path) => write!(f, "replaying from {}", path),
+        }
+    }
+}
+
+/// Line of a recording.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    /// Page of a feed (empty once the feed has ended)
+    Feed {
+        feed: String,
+        network: String,
+        page: u16,
+        #[serde(default)]
+        pairs: Vec<RecordedPair>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        err: Option<String>,
+    },
+    /// Worker's response for a request (or the host's error in its place)
+    Host {
+        region: String,
+        request: shared::Request,
+        response: Box<shared::Response>,
+    },
+}
+
+/// Pair as returned by a feed (which is its only source).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedPair {
+    pub base_token: String,
+    pub quote_token: String,
+    pub base_token_address: String,
+    pub quote_token_address: String,
+    pub contract_address: String,
+    pub mc_or_fdv: Option<f64>,
+    pub liquidity: Option<f64>,
+    pub rank: usize,
+}
+
+impl RecordedPair {
+    fn new(feed: &str, pair: &Pair) -> Self {
+        RecordedPair {
+            base_token: pair.base_token.clone(),
+            quote_token: pair.quote_token.clone(),
+            base_token_address: pair.base_token_address.clone(),
+            quote_token_address: pair.quote_token_address.clone(),
+            contract_address: pair.contract_address.clone(),
+            mc_or_fdv: pair.mc_or_fdv,
+            liquidity: pair.liquidity,
+            rank: pair
+                .sources
+                .iter()
+                .find(|s| s.feed == feed)
+                .map(|s| s.rank)
+                .unwrap_or_default(),
+        }
+    }
+
+    fn pair(&self, feed: &'static str) -> Pair {
+        Pair {
+            base_token: self.base_token.clone(),
+            quote_token: self.quote_token.clone(),
+            base_token_address: self.base_token_address.clone(),
+            quote_token_address: self.quote_token_address.clone(),
+            contract_address: self.contract_address.clone(),
+            mc_or_fdv: self.mc_or_fdv,
+            liquidity: self.liquidity,
+            sources: vec![Source {
+                feed,
+                rank: self.rank,
+            }],
+        }
+    }
+}
+
+/// Appends the entries to a JSONL file (shared by the recording feeds and hosts).
+pub struct Recorder {
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn new(path: &str) -> shared::Result<Arc<Self>> {
+        Ok(Arc::new(Recorder {
+            file: Mutex::new(OpenOptions::new().create(true).append(true).open(path)?),
+        }))
+    }
+
+    fn write(&self, entry: &Entry) {
+        let mut line = match serde_json::to_string(entry) {
+            Ok(l) => l,
+            Err(e) => {
+                log::error!("failed to serialize recorded entry: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+        // single write so that the lines from different tasks don't interleave
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            log::error!("failed to record entry: {}", e);
+        }
+    }
+}
+
+/// Records the pages returned by the wrapped feed.
+pub struct RecordingFeed {
+    inner: Arc<dyn FeedClient + Send + Sync + 'static>,
+    recorder: Arc<Recorder>,
+}
+
+impl RecordingFeed {
+    pub fn new(
+        inner: Arc<dyn FeedClient + Send + Sync + 'static>,
+        recorder: Arc<Recorder>,
+    ) -> Self {
+        RecordingFeed { inner, recorder }
+    }
+}
+
+#[async_trait::async_trait]
+impl FeedClient for RecordingFeed {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn fetch_addresses(
+        &self,
+        network: Network,
+        page: u16,
+    ) -> Result<Vec<Pair>, shared::Error> {
+        let res = self.inner.fetch_addresses(network, page).await;
+        let feed = self.name();
+        let (pairs, err) = match &res {
+            Ok(pairs) => (
+                pairs.iter().map(|p| RecordedPair::new(feed, p)).collect(),
+                None,
+            ),
+            Err(e) => (vec![], Some(e.to_string())),
+        };
+        self.recorder.write(&Entry::Feed {
+            feed: feed.into(),
+            network: network.to_string(),
+            page,
+            pairs,
+            err,
+        });
+        res
+    }
+}
+
+/// Records the worker responses of the wrapped host.
+pub struct RecordingHost<P> {
+    inner: Arc<dyn Host<P> + Send + Sync + 'static>,
+    recorder: Arc<Recorder>,
+}
+
+impl<P> RecordingHost<P> {
+    pub fn new(inner: Arc<dyn Host<P> + Send + Sync + 'static>, recorder: Arc<Recorder>) -> Self {
+        RecordingHost { inner, recorder }
+    }
+}
+
+#[async_trait::async_trait]
+impl<P> Host<P> for RecordingHost<P>
+where
+    P: Provider + Send + Sync + 'static,
+{
+    fn regions(&self) -> Vec<String> {
+        self.inner.regions()
+    }
+
+    fn bulk_size(&self) -> usize {
+        self.inner.bulk_size()
+    }
+
+    fn health(&self) -> Vec<super::host::RegionHealth> {
+        self.inner.health()
+    }
+
+    fn encoding(&self) -> shared::Encoding {
+        self.inner.encoding()
+    }
+
+    async fn __trigger(
+        &self,
+        request: Vec<(String, shared::WorkerRequest)>,
+    ) -> Vec<shared::Result<shared::WorkerResponse>> {
+        let res = self.inner.__trigger(request.clone()).await;
+        for ((region, req), result) in request.into_iter().zip(&res) {
+            let requests = match req {
+                shared::WorkerRequest::Fetch { requests, .. } => requests,
+                shared::WorkerRequest::Handshake { .. } => continue,
+            };
+            let (mut responses, err) = match result {
+                Ok(resp) => (
+                    resp.responses
+                        .iter()
+                        .map(|r| (r.id, r.inner.clone()))
+                        .collect::<HashMap<_, _>>(),
+                    resp.err.clone(),
+                ),
+                Err(e) => (HashMap::new(), Some(e.to_string())),
+            };
+            for r in requests {
+                let response = responses.remove(&r.id).unwrap_or_else(|| shared::Response {
+                    err: Some(err.clone().unwrap_or_else(|| "missing response".into())),
+                    ..Default::default()
+                });
+                self.recorder.write(&Entry::Host {
+                    region: region.clone(),
+                    request: r.inner,
+                    response: Box::new(response),
+                });
+            }
+        }
+        res
+    }
+}
+
+/// Entries of a recording, for setting up the replaying feeds and host.
+pub struct Recording {
+    entries: Vec<Entry>,
+}
+
+impl Recording {
+    pub fn load(path: &str) -> shared::Result<Self> {
+        let mut entries = vec![];
+        for line in BufReader::new(File::open(path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(Recording { entries })
+    }
+
+    /// Feeds in the order they first appear in the recording.
+    pub fn feeds(&self) -> Vec<Arc<dyn FeedClient + Send + Sync + 'static>> {
+        let mut feeds: Vec<ReplayFeed> = vec![];
+        for entry in &self.entries {
+            let (feed, network, page, pairs, err) = match entry {
+                Entry::Feed {
+                    feed,
+                    network,
+                    page,
+                    pairs,
+                    err,
+                } => (feed, network, page, pairs, err),
+                Entry::Host { .. } => continue,
+            };
+            let i = match feeds.iter().position(|f| f.name == feed.as_str()) {
+                Some(i) => i,
+                None => {
+                    feeds.push(ReplayFeed {
+                        // feed names are static, and there are only a handful of them
+                        name: Box::leak(feed.clone().into_boxed_str()),
+                        pages: HashMap::new(),
+                    });
+                    feeds.len() - 1
+                }
+            };
+            let served = match err {
+                Some(e) => Err(e.clone()),
+                None => Ok(pairs.clone()),
+            };
+            feeds[i].pages.insert((network.clone(), *page), served);
+        }
+        feeds.into_iter().map(|f| Arc::new(f) as Arc<_>).collect()
+    }
+
+    pub fn host(&self) -> ReplayHost {
+        let mut regions = vec![];
+        let mut responses: HashMap<_, VecDeque<_>> = HashMap::new();
+        for entry in &self.entries {
+            if let Entry::Host {
+                region,
+                request,
+                response,
+            } = entry
+            {
+                if !regions.contains(region) {
+                    regions.push(region.clone());
+                }
+                responses
+                    .entry(ReplayHost::key(request))
+                    .or_default()
+                    .push_back(response.as_ref().clone());
+            }
+        }
+        ReplayHost {
+            regions,
+            responses: AsyncMutex::new(responses),
+        }
+    }
+}
+
+/// Serves the recorded pages of a feed (missing pages are served as the end of the feed).
+pub struct ReplayFeed {
+    name: &'static str,
+    pages: HashMap<(String, u16), Result<Vec<RecordedPair>, String>>,
+}
+
+#[async_trait::async_trait]
+impl FeedClient for ReplayFeed {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn fetch_addresses(
+        &self,
+        network: Network,
+        page: u16,
+    ) -> Result<Vec<Pair>, shared::Error> {
+        match self.pages.get(&(network.to_string(), page)) {
+            Some(Ok(pairs)) => Ok(pairs.iter().map(|p| p.pair(self.name)).collect()),
+            Some(Err(e)) => Err(shared::Error::UnexpectedResponse(e.clone())),
+            None => Ok(vec![]),
+        }
+    }
+}
+
+/// Network, pool and provider of a request.
+type Key = (String, String, shared::ProviderId);
+
+/// Serves the recorded worker responses, matched by the pool and provider of the requests (since
+/// the request ids differ across runs). Responses of the same request are served in the recorded
+/// order, with the last one repeated afterwards.
+pub struct ReplayHost {
+    regions: Vec<String>,
+    responses: AsyncMutex<HashMap<Key, VecDeque<shared::Response>>>,
+}
+
+impl ReplayHost {
+    fn key(r: &shared::Request) -> Key {
+        (r.network.clone(), r.pool_address.clone(), r.provider)
+    }
+}
+
+#[async_trait::async_trait]
+impl<P> Host<P> for ReplayHost
+where
+    P: Provider + Send + Sync + 'static,
+{
+    fn regions(&self) -> Vec<String> {
+        if self.regions.is_empty() {
+            return vec!["replay".into()];
+        }
+        self.regions.clone()
+    }
+
+    async fn __trigger(
+        &self,
+        request: Vec<(String, shared::WorkerRequest)>,
+    ) -> Vec<shared::Result<shared::WorkerResponse>> {
+        let mut recorded = self.responses.lock().await;
+        request
+            .into_iter()
+            .map(|(_, req)| {
+                let requests = match req {
+                    shared::WorkerRequest::Fetch { requests, .. } => requests,
+                    shared::WorkerRequest::Handshake { .. } => vec![],
+                };
+                let responses = requests
+                    .into_iter()
+                    .map(|r| {
+                        let queue = recorded.get_mut(&Self::key(&r.inner));
+                        let inner = match queue {
+                            Some(q) if q.len() > 1 => q.pop_front(),
+                            Some(q) => q.front().cloned(),
+                            None => None,
+                        };
+                        shared::Tagged {
+                            id: r.id,
+                            inner: inner.unwrap_or_else(|| shared::Response {
+                                err: Some(format!(
+                                    "no recorded response for pool {} (network: {})",
+                                    r.inner.pool_address, r.inner.network
+                                )),
+                                ..Default::default()
+                            }),
+                        }
+                    })
+                    .collect();
+                Ok(shared::WorkerResponse::new(responses))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Entry, Mode, RecordedPair, Recorder, Recording, RecordingFeed, RecordingHost};
+    use crate::host::Host;
+    use crate::notifier::FileNotifier;
+    use crate::provider::MultiProvider;
+    use crate::runner::{Config, Runner};
+    use serde_json::{json, Value};
+    use shared::ohlcv::{RangeBreak, Signals};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const POOL: &str = "ReP1ayPoo1111111111111111111111111111111111";
+    const MISSING_POOL: &str = "ReP1ayMissingPoo1111111111111111111111111111";
+
+    fn temp(dir: &tempfile::TempDir, name: &str) -> String {
+        dir.path().join(name).to_string_lossy().into()
+    }
+
+    fn pair(base_token: &str, address: &str, rank: usize) -> RecordedPair {
+        RecordedPair {
+            base_token: base_token.into(),
+            quote_token: "SOL".into(),
+            base_token_address: format!("{}-mint", base_token),
+            quote_token_address: "So11111111111111111111111111111111111111112".into(),
+            contract_address: address.into(),
+            mc_or_fdv: Some(2_500_000.),
+            liquidity: Some(150_000.),
+            rank,
+        }
+    }
+
+    fn request(address: &str) -> shared::Request {
+        shared::Request {
+            network: "solana".into(),
+            pool_address: address.into(),
+            ..Default::default()
+        }
+    }
+
+    fn read_lines<T: serde::de::DeserializeOwned>(path: &str) -> Vec<T> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn parse_args() {
+        let args = |a: &[&str]| Mode::from_args(a.iter().map(|s| s.to_string()));
+        assert_eq!(args(&["scanner", "--dry-run"]), None);
+        assert_eq!(
+            args(&["scanner", "--record=cycle.jsonl"]),
+            Some(Mode::Record("cycle.jsonl".into()))
+        );
+        assert_eq!(
+            args(&["scanner", "--dry-run", "--replay=cycle.jsonl"]),
+            Some(Mode::Replay("cycle.jsonl".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_a_full_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, rerecording, alerts, storage) = (
+            temp(&dir, "recording.jsonl"),
+            temp(&dir, "rerecording.jsonl"),
+            temp(&dir, "alerts.jsonl"),
+            temp(&dir, "storage.redb"),
+        );
+        let recorder = Recorder::new(&path).unwrap();
+        for (page, pairs) in [
+            (1, vec![pair("WIF", POOL, 1), pair("BONK", MISSING_POOL, 2)]),
+            (2, vec![]),
+        ] {
+            recorder.write(&Entry::Feed {
+                feed: "gt_top".into(),
+                network: "solana".into(),
+                page,
+                pairs,
+                err: None,
+            });
+        }
+        recorder.write(&Entry::Host {
+            region: "us-east-1".into(),
+            request: request(POOL),
+            response: Box::new(shared::Response {
+                status: Some(200),
+                signals: Some(Signals {
+                    range_high_break: Some(RangeBreak {
+                        prev_bound: 1.5,
+                        idx: 29,
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        });
+        recorder.write(&Entry::Host {
+            region: "us-east-1".into(),
+            request: request(MISSING_POOL),
+            response: Box::new(shared::Response {
+                status: Some(404),
+                body: Some("not found".into()),
+                ..Default::default()
+            }),
+        });
+        drop(recorder);
+
+        // replay the recording while recording it again
+        let recording = Recording::load(&path).unwrap();
+        let recorder = Recorder::new(&rerecording).unwrap();
+        let feeds = recording
+            .feeds()
+            .into_iter()
+            .map(|f| Arc::new(RecordingFeed::new(f, recorder.clone())) as Arc<_>)
+            .collect();
+        let host = Arc::new(recording.host()) as Arc<dyn Host<MultiProvider> + Send + Sync>;
+        let hosts = vec![Arc::new(RecordingHost::new(host, recorder)) as Arc<_>];
+        let notifier = HashMap::from([(
+            "solana".to_string(),
+            Arc::new(FileNotifier::new(&alerts, "solana").unwrap()) as Arc<_>,
+        )]);
+        let config: Config = serde_json::from_value(json!({
+            "lambda_function": "replay",
+            "storage_path": storage,
+            "host_requests_per_min": 30,
+            "post_now": true,
+            "post_once": true,
+        }))
+        .unwrap();
+        let runner = Runner::with_clients(config, feeds, hosts, notifier, None).unwrap();
+        tokio::time::timeout(Duration::from_secs(30), runner.run())
+            .await
+            .expect("cycle didn't finish");
+
+        let posted = read_lines::<Value>(&alerts);
+        let entries = read_lines::<Entry>(&rerecording);
+
+        // the missing pool isn't retried (or posted)
+        assert_eq!(posted.len(), 1);
+        let msg = posted[0]["message"].as_str().unwrap();
+        assert!(msg.starts_with("###  WIF/SOL $2.50M [gt_top #1]"));
+        assert!(msg.contains(POOL));
+        assert!(msg.ends_with("`Range high 1.5 broken`"));
+
+        let pages = entries
+            .iter()
+            .filter_map(|e| match e {
+                Entry::Feed { page, pairs, .. } => Some((*page, pairs.len())),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(pages, vec![(1, 2), (2, 0)]);
+        let mut responses = entries
+            .iter()
+            .filter_map(|e| match e {
+                Entry::Host {
+                    region,
+                    request,
+                    response,
+                } => Some((
+                    region.as_str(),
+                    request.pool_address.as_str(),
+                    response.status,
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        responses.sort();
+        let mut expected = vec![
+            ("us-east-1", POOL, Some(200)),
+            ("us-east-1", MISSING_POOL, Some(404)),
+        ];
+        expected.sort();
+        assert_eq!(responses, expected);
+    }
+}
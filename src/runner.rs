@@ -1,104 +1,233 @@
 use std::collections::{HashMap, HashSet};
+use std::env;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use chrono::{offset::Utc, TimeDelta};
+use chrono::offset::Utc;
 use futures::future;
 use serde::Deserialize;
+use shared::ohlcv::{Bars, OHLCVList};
 use strum::VariantArray;
+use tokio::sync::mpsc;
 
-use super::storage::Storage;
+use super::control::Control;
+use super::scanner::{Event, Page, Scan, ScanOutcome, Scanner};
+use super::schedule::{self, Schedule};
+use super::storage::{Checkpoint, ScanCheckpoint, Storage};
 
 use super::{
-    feed::{FeedClient, Network},
-    host::Host,
-    notifier::Notifier,
-    provider::Provider,
+    analyzer::{self, Analyzer},
+    feed::{FeedClient, Network, PoolId},
+    host::{
+        limiter::{self, Limiter, Outcome},
+        AwsConfig, Host,
+    },
+    metrics,
+    notifier::{BufferedDiscordWebhook, DryRun, Notifier},
+    provider::{Provider, RpcConfig, RpcSwaps},
+    queue::Queue,
+    replay,
+    security::{self, SecurityProvider},
 };
 
-const ONE_MIN_FIVE_SECS: Duration = Duration::from_secs(65);
+const MAX_DRAIN_ATTEMPTS: usize = 60;
+const PAGE_CHANNEL_SIZE: usize = 16;
 const fn default_min_liquidity() -> u64 {
     1000
 }
+const fn default_max_pools_per_token() -> u16 {
+    1
+}
+const fn default_max_concurrent_scans() -> u8 {
+    4
+}
+fn default_providers() -> Vec<shared::ProviderId> {
+    vec![shared::ProviderId::GeckoTerminal]
+}
 
 #[derive(Deserialize)]
 pub struct Config {
     pub lambda_function: String,
+    #[serde(default)]
+    pub aws: AwsConfig,
     pub storage_path: String,
     #[serde(default)]
     pub max_pages: Option<u16>,
     #[serde(default)]
     pub max_attempts_per_pair: Option<u16>,
+    /// Requests sent to each host region per minute at start (adapted to the upstream responses)
     pub host_requests_per_min: u8,
     #[serde(default)]
+    pub rate_limit: limiter::Config,
+    #[serde(default)]
     pub discord_url_network: HashMap<String, String>,
+    /// Separate channel for watchlist alerts (defaults to the network's channel)
+    #[serde(default)]
+    pub discord_url_watchlist: Option<String>,
     #[serde(default)]
     pub post_once: bool,
     #[serde(default)]
     pub post_now: bool,
     #[serde(default = "default_min_liquidity")]
     pub min_liquidity: u64,
+    /// Number of pools analyzed per base token (0 for no limit)
+    #[serde(default = "default_max_pools_per_token")]
+    pub max_pools_per_token: u16,
+    /// Screen base tokens for rugs and honeypots before analysis
+    #[serde(default)]
+    pub security: Option<security::Config>,
+    /// OHLCV providers in the order of preference (and fallback). CoinGecko's candles are
+    /// approximated from daily prices, so the range breaks aren't posted for them.
+    #[serde(default = "default_providers")]
+    pub providers: Vec<shared::ProviderId>,
+    /// Endpoints for building candles from on-chain swaps (`rpc` provider)
+    #[serde(default)]
+    pub rpc: Option<RpcConfig>,
+    /// When the networks are scanned (daily at 00:01 UTC by default)
+    #[serde(default)]
+    pub schedule: Option<schedule::Config>,
+    /// Schedules overriding the default for specific networks
+    #[serde(default)]
+    pub schedule_network: HashMap<String, schedule::Config>,
+    /// Number of networks scanned in parallel
+    #[serde(default = "default_max_concurrent_scans")]
+    pub max_concurrent_scans: u8,
+    /// Let the workers run the detectors and return only the signals (and the last few candles)
+    #[serde(default)]
+    pub analyze_in_worker: bool,
 }
 
-pub struct Runner<P, N> {
+impl Config {
+    /// Reads the config from the file at `CONFIG` env.
+    pub fn load() -> shared::Result<Self> {
+        let path = env::var("CONFIG").map_err(|_| shared::Error::Config("CONFIG unset".into()))?;
+        let f = std::fs::File::open(path)?;
+        Ok(serde_json::from_reader(f)?)
+    }
+}
+
+pub struct Runner<P> {
     pub storage: Storage,
-    feeds: Vec<Box<dyn FeedClient + Send + Sync + 'static>>,
-    hosts: Vec<Box<dyn Host<P> + Send + Sync + 'static>>,
-    config: Config,
-    notifier: HashMap<String, Arc<N>>,
-    buffer: Vec<shared::Request>,
-    pools: HashSet<String>,
-    ended_feeds: Vec<bool>,
-    pairs: HashSet<String>,
-    current: Instant,
+    pub control: Control,
+    feeds: Vec<Arc<dyn FeedClient + Send + Sync + 'static>>,
+    hosts: Vec<Arc<dyn Host<P> + Send + Sync + 'static>>,
+    security: Option<Arc<dyn SecurityProvider + Send + Sync + 'static>>,
+    rpc: Option<Arc<RpcSwaps>>,
+    config: Arc<Config>,
+    notifier: HashMap<String, Arc<dyn Notifier + Send + Sync + 'static>>,
+    watch_notifier: Option<Arc<dyn Notifier + Send + Sync + 'static>>,
+    queue: Queue,
+    default_schedule: Schedule,
+    schedules: HashMap<Network, Schedule>,
+    limiter: Limiter,
+    /// When the last round of requests was sent to the hosts
+    current: Option<Instant>,
 }
 
-impl Runner<super::provider::GeckoTerminal, super::notifier::BufferedDiscordWebhook> {
-    pub fn new(c: Config) -> shared::Result<Self> {
-        Ok(Runner {
-            feeds: vec![
-                Box::new(super::feed::CoinMarketCap::default()) as Box<_>,
-                Box::new(super::feed::GeckoTerminalTop::default()) as Box<_>,
-                Box::new(super::feed::GeckoTerminalTrending::default()) as Box<_>,
-            ],
-            hosts: vec![Box::new(super::host::AwsLambda::new(&c.lambda_function)?)],
-            storage: super::storage::Storage::new(&c.storage_path).expect("init storage"),
-            notifier: Network::VARIANTS
-                .iter()
-                .filter_map(|n| {
-                    let url = c.discord_url_network.get(&n.to_string())?;
-                    Some((
-                        n.to_string(),
-                        Arc::new(super::notifier::BufferedDiscordWebhook::new(url.clone())),
-                    ))
-                })
-                .collect(),
-            buffer: Vec::with_capacity(c.host_requests_per_min as usize),
-            config: c,
-            pools: HashSet::with_capacity(1000),
-            pairs: HashSet::with_capacity(1000),
-            ended_feeds: vec![],
-            // add 1-min so that it doesn't block on first attempt
-            current: Instant::now()
-                .checked_sub(ONE_MIN_FIVE_SECS)
-                .expect("simple time math fail"),
-        })
+impl Runner<super::provider::MultiProvider> {
+    /// Sets up the runner, with the alerts going to the Discord webhooks (or wherever the
+    /// dry run says), and the feeds and hosts recorded or replayed if asked.
+    pub async fn new(
+        c: Config,
+        dry_run: Option<DryRun>,
+        replay: Option<replay::Mode>,
+    ) -> shared::Result<Self> {
+        let notifier = |channel: &str, url: &String| match &dry_run {
+            Some(d) => d.notifier(channel),
+            None => Ok(Arc::new(BufferedDiscordWebhook::new(url.clone())) as Arc<_>),
+        };
+        let mut notifiers = HashMap::new();
+        for n in Network::VARIANTS {
+            if let Some(url) = c.discord_url_network.get(&n.to_string()) {
+                notifiers.insert(n.to_string(), notifier(&n.to_string(), url)?);
+            }
+        }
+        let watch_notifier = match &c.discord_url_watchlist {
+            Some(url) => Some(notifier("watchlist", url)?),
+            None => None,
+        };
+
+        let (mut feeds, mut hosts): (Vec<Arc<dyn FeedClient + Send + Sync + 'static>>, _) =
+            match &replay {
+                Some(replay::Mode::Replay(path)) => {
+                    let recording = replay::Recording::load(path)?;
+                    (
+                        recording.feeds(),
+                        vec![Arc::new(recording.host()) as Arc<dyn Host<_> + Send + Sync>],
+                    )
+                }
+                _ => {
+                    let mut lambda =
+                        super::host::AwsLambda::new(&c.lambda_function, &c.aws).await?;
+                    if c.aws.verify_deployment {
+                        lambda.retain_deployed().await?;
+                    }
+                    lambda.handshake().await?;
+                    (
+                        vec![
+                            Arc::new(super::feed::CoinMarketCap::default()) as Arc<_>,
+                            Arc::new(super::feed::GeckoTerminalTop::default()) as Arc<_>,
+                            Arc::new(super::feed::GeckoTerminalTrending::default()) as Arc<_>,
+                        ],
+                        vec![Arc::new(lambda) as Arc<_>],
+                    )
+                }
+            };
+        if let Some(replay::Mode::Record(path)) = &replay {
+            let recorder = replay::Recorder::new(path)?;
+            feeds = feeds
+                .into_iter()
+                .map(|f| Arc::new(replay::RecordingFeed::new(f, recorder.clone())) as Arc<_>)
+                .collect();
+            hosts = hosts
+                .into_iter()
+                .map(|h| Arc::new(replay::RecordingHost::new(h, recorder.clone())) as Arc<_>)
+                .collect();
+        }
+        Self::with_clients(c, feeds, hosts, notifiers, watch_notifier)
     }
 }
 
-impl<P, N> Runner<P, N>
+impl<P> Runner<P>
 where
     P: Provider + Send + Sync + 'static,
-    N: Notifier + Send + Sync + 'static,
 {
-    pub async fn run(mut self) {
-        let mut current_page = 1;
-        let mut current_network_idx = 0;
-        let mut posted_once = !self.config.post_now;
-        self.ended_feeds = vec![false; self.feeds.len()];
+    /// Sets up the runner with the given feeds, hosts and notifiers (by network).
+    pub fn with_clients(
+        mut c: Config,
+        feeds: Vec<Arc<dyn FeedClient + Send + Sync + 'static>>,
+        hosts: Vec<Arc<dyn Host<P> + Send + Sync + 'static>>,
+        notifier: HashMap<String, Arc<dyn Notifier + Send + Sync + 'static>>,
+        watch_notifier: Option<Arc<dyn Notifier + Send + Sync + 'static>>,
+    ) -> shared::Result<Self> {
+        let (default_schedule, schedules) = Self::schedules(&c)?;
+        let rpc = match c.rpc.take() {
+            Some(c) => Some(Arc::new(RpcSwaps::new(c)?)),
+            None => None,
+        };
+        Ok(Runner {
+            rpc,
+            feeds,
+            hosts,
+            limiter: Limiter::new(c.rate_limit.clone(), c.host_requests_per_min as u16),
+            security: c
+                .security
+                .as_ref()
+                .map(|_| Arc::new(super::security::GoPlus::default()) as Arc<_>),
+            storage: super::storage::Storage::new(&c.storage_path)?,
+            control: Control::default(),
+            notifier,
+            watch_notifier,
+            queue: Queue::default(),
+            config: Arc::new(c),
+            default_schedule,
+            schedules,
+            current: None,
+        })
+    }
 
-        let batch_len = self.config.host_requests_per_min as usize
-            * self.hosts.iter().map(|h| h.bulk_size()).sum::<usize>();
+    pub async fn run(mut self) {
+        let _running = self.control.mark_running();
         let networks = self
             .notifier
             .keys()
@@ -115,61 +244,287 @@ where
         }
         log::info!("enabled networks: {:?}", networks);
 
-        loop {
-            if current_network_idx == networks.len() {
-                posted_once = true;
-                current_network_idx = 0;
-            }
+        let now = Utc::now();
+        let mut next_runs = networks
+            .iter()
+            .filter_map(|&n| {
+                let next = if self.config.post_now {
+                    Some(now)
+                } else {
+                    self.schedule(n).next_after(now)
+                };
+                match next {
+                    Some(t) => Some((n, t)),
+                    None => {
+                        log::warn!("no upcoming scans for network {}", n);
+                        None
+                    }
+                }
+            })
+            .collect::<HashMap<_, _>>();
+        // each network is scanned in its own task, which sends the pools back for dispatching
+        let (tx, mut rx) = mpsc::channel(PAGE_CHANNEL_SIZE);
+        let mut active = HashSet::new();
+        for scan in self.restore(&networks) {
+            active.insert(scan.network);
+            tokio::task::spawn(self.scanner().run(scan, tx.clone()));
+        }
+        // whether a scan has finished since the runner was last idle
+        let mut scanned = false;
+        // whether a scan has given up (or stopped) since then
+        let mut failed = false;
 
-            if self.config.post_once && posted_once {
-                log::info!("exiting after first run");
+        loop {
+            self.control.wait_if_paused().await;
+            if self.control.is_shutting_down() {
+                drop(tx);
+                self.shutdown(rx).await;
                 return;
             }
-
-            if posted_once {
-                self.block_until_midnight().await;
-                posted_once = false;
+            if self.control.take_reload() {
+                self.reload();
             }
 
-            let network = networks[current_network_idx];
-            match self.populate_pairs(network, current_page).await {
-                Ok(true) => (),
-                Ok(false) => {
-                    current_page = 1;
-                    current_network_idx += 1;
-                    self.pools.clear();
-                    self.ended_feeds = vec![false; self.feeds.len()];
-                    continue;
+            while let Some(network) = self.control.take_scan_request() {
+                self.scan_on_request(network, &networks, &mut active, &tx);
+            }
+            let now = Utc::now();
+            for &network in &networks {
+                match next_runs.get(&network) {
+                    Some(&t) if t <= now => (),
+                    _ => continue,
                 }
-                Err(e) => {
-                    log::error!("failed to populate pairs: {}", e);
+                if active.contains(&network) {
+                    log::warn!("previous scan of network {} is still running", network);
+                } else if active.len() >= self.config.max_concurrent_scans.max(1) as usize {
+                    // wait for a slot (it's still due)
                     continue;
+                } else {
+                    self.start_scan(network, &mut active, &tx);
                 }
+                match self.schedule(network).next_after(now) {
+                    Some(t) => next_runs.insert(network, t),
+                    None => next_runs.remove(&network),
+                };
             }
 
-            while self.buffer.len() >= batch_len {
-                log::info!(
-                    "reached batch size {} for {}, flushing",
-                    self.buffer.len(),
-                    network
-                );
-                self.flush().await;
+            let mut waiting_until = None;
+            if active.is_empty() {
+                if scanned {
+                    scanned = false;
+                    // only a cycle where every scan went through counts as a success
+                    if !std::mem::take(&mut failed) {
+                        let now = Utc::now();
+                        metrics::LAST_SUCCESS.set(now.timestamp());
+                        self.control
+                            .update_state(|s| s.last_cycle_finished = Some(now));
+                    }
+                    if self.config.post_once {
+                        // the rest of the queue didn't make a full batch
+                        if !self.queue.is_empty() {
+                            log::info!("flushing {} remaining requests", self.queue.len());
+                            if let Some(t) = self.throttled_until() {
+                                log::warn!("all host regions are throttled, blocking until one is available");
+                                tokio::time::sleep_until(t.into()).await;
+                            }
+                            self.flush().await;
+                        }
+                        log::info!("exiting after first run");
+                        return;
+                    }
+                }
+                waiting_until = match next_runs.values().min() {
+                    Some(&t) => Some(t),
+                    None => {
+                        log::error!("no upcoming scans, exiting runner...");
+                        return;
+                    }
+                };
             }
+            metrics::BUFFER_SIZE.set(self.queue.len() as i64);
+            self.control.update_state(|s| {
+                s.next_runs = next_runs.iter().map(|(n, t)| (n.to_string(), *t)).collect();
+                s.buffer_len = self.queue.len();
+                s.waiting_until = waiting_until;
+            });
 
-            current_page += 1;
-            if let Some(max) = self.config.max_pages {
-                if current_page > max {
-                    log::info!("reached max pages for network: {}", network);
-                    current_page = 1;
-                    current_network_idx += 1;
-                    self.pairs.clear();
+            // (wait for at least one request while the regions are disabled)
+            let regions = self
+                .hosts
+                .iter()
+                .flat_map(|h| h.regions())
+                .collect::<Vec<_>>();
+            let batch_len = self.limiter.capacity(&regions).max(1);
+            // the scans keep queueing pools until a region is available again
+            let throttled_until = self.limiter.all_disabled_until(&regions);
+            if self.queue.len() >= batch_len {
+                match throttled_until {
+                    Some(t) => log::debug!(
+                        "all host regions are throttled, deferring flush by {:?}",
+                        t.saturating_duration_since(Instant::now())
+                    ),
+                    None => {
+                        // the scans carry on in the meantime
+                        log::info!("reached batch size {}, flushing", self.queue.len());
+                        self.flush().await;
+                        continue;
+                    }
                 }
             }
+
+            let sleep = waiting_until
+                .map(|t| {
+                    log::info!("blocking until {}", t);
+                    t.signed_duration_since(Utc::now())
+                        .to_std()
+                        .unwrap_or_default()
+                })
+                .unwrap_or_default();
+            tokio::select! {
+                Some(event) = rx.recv() => match event {
+                    Event::Page(page) => self.enqueue(page),
+                    Event::Done { scan, outcome } => {
+                        let network = scan.network.to_string();
+                        match outcome {
+                            ScanOutcome::Finished => {
+                                log::info!("finished scanning network: {}", network);
+                                metrics::SCAN_DURATION
+                                    .with_label_values(&[&network])
+                                    .observe(scan.started.elapsed().as_secs_f64());
+                            }
+                            ScanOutcome::GaveUp => {
+                                log::error!("gave up scanning network: {}", network);
+                                metrics::SCAN_FAILURES.with_label_values(&[&network]).inc();
+                                failed = true;
+                            }
+                            ScanOutcome::Stopped => {
+                                log::info!("stopped scanning network: {}", network);
+                                failed = true;
+                            }
+                        }
+                        active.remove(&scan.network);
+                        scanned = true;
+                    }
+                },
+                network = self.control.scan_requested() => {
+                    self.scan_on_request(network, &networks, &mut active, &tx)
+                }
+                _ = self.control.shutdown_requested() => (),
+                _ = async_std::task::sleep(sleep), if waiting_until.is_some() => (),
+                _ = tokio::time::sleep_until(throttled_until.unwrap_or_else(Instant::now).into()),
+                    if throttled_until.is_some() => (),
+            }
+        }
+    }
+
+    /// Queues the requests from a page of a scan.
+    fn enqueue(&mut self, page: Page) {
+        let network = page.network.to_string();
+        self.queue.extend(page.requests);
+        for (pool, sources) in page.duplicates {
+            if let Some(req) = self.queue.find_mut(&network, |r| r.pool_address == pool) {
+                for (feed, rank) in sources {
+                    if !req.sources.iter().any(|(f, _)| *f == feed) {
+                        req.sources.push((feed, rank));
+                    }
+                }
+            }
+        }
+        for (token, pool) in page.siblings {
+            if let Some(req) = self.queue.find_mut(&network, |r| {
+                r.base_token_address.as_deref() == Some(token.as_str())
+            }) {
+                req.siblings.push(pool);
+            }
+        }
+    }
+
+    /// Builds the default schedule and the per-network overrides.
+    fn schedules(c: &Config) -> shared::Result<(Schedule, HashMap<Network, Schedule>)> {
+        let default = match &c.schedule {
+            Some(s) => Schedule::new(s)?,
+            None => Schedule::default(),
+        };
+        let mut schedules = HashMap::new();
+        for (name, s) in &c.schedule_network {
+            match Network::VARIANTS.iter().find(|n| n.to_string() == *name) {
+                Some(&n) => schedules.insert(n, Schedule::new(s)?),
+                None => return Err(shared::Error::Config(format!("unknown network: {}", name))),
+            };
+        }
+        Ok((default, schedules))
+    }
+
+    fn schedule(&self, network: Network) -> &Schedule {
+        self.schedules
+            .get(&network)
+            .unwrap_or(&self.default_schedule)
+    }
+
+    fn scanner(&self) -> Scanner {
+        Scanner {
+            feeds: self.feeds.clone(),
+            security: self.security.clone(),
+            rpc: self.rpc.clone(),
+            storage: self.storage.clone(),
+            control: self.control.clone(),
+            config: self.config.clone(),
+        }
+    }
+
+    /// Starts a new scan of the network (in its own task), which begins with the watched pools.
+    fn start_scan(
+        &mut self,
+        network: Network,
+        active: &mut HashSet<Network>,
+        tx: &mpsc::Sender<Event>,
+    ) {
+        log::info!("starting scan for network: {}", network);
+        match self.storage.purge_expired_blocks() {
+            Ok(n) => log::debug!("{} addresses in blocklist", n),
+            Err(e) => log::error!("failed to purge expired blocks: {}", e),
+        }
+        let scanner = self.scanner();
+        let timeframe = self.schedule(network).timeframe();
+        let mut scan = Scan::new(network, self.feeds.len(), timeframe);
+        self.queue.extend(scanner.watchlist(&mut scan));
+        active.insert(network);
+        tokio::task::spawn(scanner.run(scan, tx.clone()));
+    }
+
+    /// Scans the network outside of its schedule.
+    fn scan_on_request(
+        &mut self,
+        network: Network,
+        networks: &[Network],
+        active: &mut HashSet<Network>,
+        tx: &mpsc::Sender<Event>,
+    ) {
+        if !networks.contains(&network) {
+            log::warn!("ignoring scan request for disabled network {}", network);
+        } else if active.contains(&network) {
+            log::warn!("network {} is already being scanned", network);
+        } else {
+            log::info!("scanning network {} on request", network);
+            self.start_scan(network, active, tx);
+        }
+    }
+
+    /// Returns a handle for analyzing individual pools with the runner's hosts and notifiers.
+    pub fn analyzer(&self) -> Analyzer<P> {
+        Analyzer {
+            hosts: self.hosts.clone(),
+            rpc: self.rpc.clone(),
+            notifier: self.notifier.clone(),
+            providers: self.config.providers.clone(),
         }
     }
 
     pub fn spawn_cleanup(&self) -> tokio::task::JoinHandle<()> {
-        let notifiers = self.notifier.clone();
+        let mut notifiers = self.notifier.clone();
+        if let Some(n) = &self.watch_notifier {
+            notifiers.insert("watchlist".into(), n.clone());
+        }
         tokio::task::spawn(async move {
             loop {
                 for (name, notifier) in &notifiers {
@@ -183,178 +538,328 @@ where
         })
     }
 
-    #[allow(deprecated)]
-    async fn block_until_midnight(&self) {
+    /// Reloads the config file, although hosts, notifiers and providers only change on restart.
+    fn reload(&mut self) {
+        let c = match Config::load() {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("failed to reload config: {}", e);
+                return;
+            }
+        };
+        if c.lambda_function != self.config.lambda_function
+            || c.aws != self.config.aws
+            || c.storage_path != self.config.storage_path
+            || c.discord_url_network != self.config.discord_url_network
+            || c.discord_url_watchlist != self.config.discord_url_watchlist
+        {
+            log::warn!("changes to lambda function, storage or notifiers need a restart");
+        }
+        match Self::schedules(&c) {
+            Ok((default, schedules)) => {
+                self.default_schedule = default;
+                self.schedules = schedules;
+            }
+            Err(e) => {
+                log::error!("failed to reload config: {}", e);
+                return;
+            }
+        }
+        self.limiter
+            .set_config(c.rate_limit.clone(), c.host_requests_per_min as u16);
+        self.config = Arc::new(c);
+        log::info!("reloaded config (applies to new scans)");
+    }
+
+    /// Restores the pending requests and scans saved during the last shutdown,
+    /// unless the network's next scan was due in the meantime.
+    fn restore(&mut self, networks: &[Network]) -> Vec<Scan> {
+        let checkpoint = match self.storage.take_checkpoint() {
+            Ok(Some(c)) => c,
+            Ok(None) => return vec![],
+            Err(e) => {
+                log::error!("failed to load checkpoint: {}", e);
+                return vec![];
+            }
+        };
         let now = Utc::now();
-        let tmrw_mid = (now + TimeDelta::days(1))
-            .date()
-            .and_hms_opt(0, 1, 0)
-            .expect("invalid time?");
-        let duration = tmrw_mid
-            .signed_duration_since(now)
-            .to_std()
-            .expect("invalid duration?");
+        let is_fresh = |network: &str| {
+            networks
+                .iter()
+                .find(|n| n.to_string() == network)
+                .and_then(|&n| self.schedule(n).next_after(checkpoint.saved_at))
+                .map(|t| t > now)
+                .unwrap_or(false)
+        };
+
+        let buffer = checkpoint
+            .buffer
+            .into_iter()
+            .map(shared::Request::from)
+            .filter(|r| is_fresh(&r.network))
+            .collect::<Vec<_>>();
+        let mut scans = vec![];
+        for c in checkpoint.scans {
+            if !is_fresh(&c.network) {
+                log::info!("discarding stale scan of network {}", c.network);
+                continue;
+            }
+            let network = match networks.iter().find(|n| n.to_string() == c.network) {
+                Some(&n) => n,
+                None => continue,
+            };
+            log::info!("resuming network {} from page {}", network, c.page);
+            let timeframe = self.schedule(network).timeframe();
+            let mut scan = Scan::new(network, self.feeds.len(), timeframe);
+            scan.page = c.page;
+            if c.ended_feeds.len() == scan.ended_feeds.len() {
+                scan.ended_feeds = c.ended_feeds;
+            }
+            scan.pools = c.pools.iter().map(|a| PoolId::new(network, a)).collect();
+            scans.push(scan);
+        }
         log::info!(
-            "blocking until UTC midnight for {} seconds",
-            duration.as_secs()
+            "restored {} pending requests and {} scans from checkpoint",
+            buffer.len(),
+            scans.len()
         );
-        async_std::task::sleep(duration).await;
+        self.queue.extend(buffer);
+        scans
     }
 
-    async fn populate_pairs(&mut self, network: Network, page: u16) -> shared::Result<bool> {
-        log::info!(
-            "fetching addresses for network: {}, page: {}",
-            network,
-            page
-        );
-        let res = future::join_all(
-            self.feeds
+    /// Waits for the scans to stop, saves the pending requests and scans
+    /// (to be restored on the next start) and drains the notifiers.
+    async fn shutdown(&mut self, mut rx: mpsc::Receiver<Event>) {
+        log::info!("shutting down runner, waiting for scans to stop");
+        // the channel closes once all the scans have stopped
+        let mut scans = vec![];
+        while let Some(event) = rx.recv().await {
+            match event {
+                Event::Page(page) => self.enqueue(page),
+                Event::Done {
+                    scan,
+                    outcome: ScanOutcome::Stopped,
+                } => scans.push(scan),
+                Event::Done { .. } => (),
+            }
+        }
+        log::info!("saving checkpoint ({} pending requests)", self.queue.len());
+        let checkpoint = Checkpoint {
+            saved_at: Utc::now(),
+            scans: scans
                 .iter()
-                .enumerate()
-                .map(|(i, f)| {
-                    let ended = self.ended_feeds[i];
-                    async move {
-                        if ended {
-                            return Ok(vec![]);
-                        }
-                        f.fetch_addresses(network, page).await
-                    }
+                .map(|s| ScanCheckpoint {
+                    network: s.network.to_string(),
+                    page: s.page,
+                    ended_feeds: s.ended_feeds.clone(),
+                    pools: s.pools.iter().map(|p| p.address.clone()).collect(),
                 })
-                .collect::<Vec<_>>(),
-        )
-        .await;
-
-        for (i, pairs) in res.into_iter().enumerate() {
-            let pairs = pairs?;
-            log::debug!("received {} pairs", pairs.len());
-            if pairs.is_empty() {
-                log::info!(
-                    "feed {} has ended for page {} (network: {})",
-                    i + 1,
-                    page,
-                    network
-                );
-                self.ended_feeds[i] = true;
-            }
-            for pair in pairs {
-                match self.storage.is_blocked(&pair.contract_address) {
-                    Err(e) => log::error!("failed to check blacklist for address {}", e),
-                    Ok(true) => {
-                        log::info!("skipping blocked address: {}", pair.contract_address);
-                        continue;
-                    }
-                    Ok(false) => (),
-                }
-
-                if let Some(min) = pair.liquidity {
-                    if min < self.config.min_liquidity as f64 {
-                        log::info!(
-                            "skipping low liquidity pool: {} (USD: {})",
-                            pair.contract_address,
-                            min,
-                        );
-                        continue;
-                    }
-                }
+                .collect(),
+            buffer: self.queue.drain().into_iter().map(Into::into).collect(),
+        };
+        if let Err(e) = self.storage.save_checkpoint(&checkpoint) {
+            log::error!("failed to save checkpoint: {}", e);
+        }
 
-                if !self.pools.insert(pair.contract_address.clone()) {
-                    log::info!("skipping duplicate address: {}", pair.contract_address);
+        let notifiers = self
+            .notifier
+            .values()
+            .chain(self.watch_notifier.iter())
+            .collect::<Vec<_>>();
+        for _ in 0..MAX_DRAIN_ATTEMPTS {
+            let mut pending = false;
+            for notifier in &notifiers {
+                if notifier.pending().await == 0 {
                     continue;
                 }
-
-                self.buffer.push(shared::Request {
-                    network: network.to_string(),
-                    pool_address: pair.contract_address,
-                    mc_or_fdv: pair.mc_or_fdv,
-                    maybe_duplicate: pair.base_token != ""
-                        && !self.pairs.insert(pair.base_token.clone()),
-                    token: if pair.base_token == "" && pair.quote_token == "" {
-                        None
-                    } else {
-                        Some((pair.base_token, pair.quote_token))
-                    },
-                });
+                pending = true;
+                if let Err(e) = notifier.flush().await {
+                    log::error!("failed to flush notifier: {}", e);
+                }
             }
+            if !pending {
+                log::info!("drained all notifiers");
+                return;
+            }
+            async_std::task::sleep(Duration::from_secs(1)).await;
         }
-
-        if self.ended_feeds.iter().all(|&e| e) {
-            log::debug!("all feeds ended for network: {}", network);
-            return Ok(false);
-        }
-        Ok(true)
+        log::warn!("gave up draining notifiers");
     }
 
     async fn flush(&mut self) {
-        self.block_until_about_next_minute().await;
-        let requests = self
-            .hosts
-            .iter()
-            .map(|h| {
-                (0..h.bulk_size())
-                    .map(|_| {
-                        self.buffer
-                            .drain(..self.config.host_requests_per_min as usize)
-                            .collect::<Vec<_>>()
+        // quarantined regions are left out by the hosts
+        let regions = self.hosts.iter().map(|h| h.regions()).collect::<Vec<_>>();
+        if regions.iter().all(|r| r.is_empty()) {
+            log::warn!("no healthy host regions, skipping flush");
+            return;
+        }
+        self.block_until_next_round(&regions.concat()).await;
+        // candles from swaps are built by the scanner rather than the hosts
+        let mut local = self
+            .queue
+            .extract(|r| r.provider == shared::ProviderId::Rpc);
+        let requests = regions
+            .into_iter()
+            .map(|regions| {
+                // (throttled regions take nothing)
+                regions
+                    .into_iter()
+                    .map(|r| {
+                        let batch = self.queue.take(self.limiter.limit(&r));
+                        (r, batch)
                     })
+                    .filter(|(_, batch)| !batch.is_empty())
                     .collect::<Vec<_>>()
             })
             .collect::<Vec<_>>();
+        // a few pools at a time, the rest go back (once the batches for the hosts are taken)
+        if let Some(rpc) = &self.rpc {
+            let deferred = local.split_off(local.len().min(rpc.concurrent_pools()));
+            self.queue.extend(deferred);
+        }
 
         // publish to all hosts at once
-        let resp = future::join_all(
-            self.hosts
-                .iter()
-                .zip(requests.clone().into_iter())
-                .map(|(host, requests)| host.trigger(requests))
-                .collect::<Vec<_>>(),
+        let (resp, local_resp) = future::join(
+            future::join_all(
+                self.hosts
+                    .iter()
+                    .zip(requests.clone())
+                    .map(|(host, requests)| host.trigger(requests, self.config.analyze_in_worker))
+                    .collect::<Vec<_>>(),
+            ),
+            self.fetch_local(&local),
         )
         .await;
 
-        for (host_batch, orig_batch) in resp.into_iter().zip(requests.into_iter()) {
-            for (batch, orig) in host_batch.into_iter().zip(orig_batch) {
-                for (resp, pair) in batch.into_iter().zip(orig.into_iter()) {
-                    match resp.and_then(|r| r.ohlcv_data()) {
-                        Err(e) => {
-                            // TODO: ignore pair after max attempts
-                            log::error!(
-                                "failed to get data for pair ({}) {} (network: {}): {}",
-                                pair.token
-                                    .as_ref()
-                                    .map(|(b, q)| format!("{}/{}", b, q))
-                                    .unwrap_or_default(),
-                                pair.pool_address,
-                                pair.network,
-                                e
-                            );
-                            match e {
-                                // pool doesn't exist (better ignore it for the day)
-                                shared::Error::UnexpectedStatusCode(404, _) => (),
-                                _ => self.buffer.push(pair),
-                            }
-                        }
-                        Ok(resp) => {
-                            if let Err(e) = self
-                                .notifier
-                                .get(&pair.network)
-                                .expect("missing notifier")
-                                .post_analysis(&pair, resp)
-                                .await
-                            {
-                                log::error!("failed to post analysis: {}", e);
-                            }
-                        }
+        for (host_batch, orig_batch) in resp.into_iter().zip(requests) {
+            for (batch, (region, orig)) in host_batch.into_iter().zip(orig_batch) {
+                let mut outcome = Outcome {
+                    total: orig.len(),
+                    ..Default::default()
+                };
+                for (resp, pair) in batch.into_iter().zip(orig) {
+                    match &resp {
+                        Err(shared::Error::UnexpectedStatusCode(429, _)) => outcome.throttled += 1,
+                        Err(shared::Error::UnexpectedStatusCode(500..=599, _))
+                        | Err(shared::Error::Batch(_)) => outcome.failed += 1,
+                        _ => (),
                     }
+                    self.handle(pair, resp).await;
                 }
+                self.limiter.record(&region, outcome);
             }
         }
+        for (pair, resp) in local.into_iter().zip(local_resp) {
+            self.handle(pair, resp.map(Bars::Candles)).await;
+        }
+        metrics::BUFFER_SIZE.set(self.queue.len() as i64);
     }
 
-    async fn block_until_about_next_minute(&mut self) {
-        let elapsed = self.current.elapsed();
-        if elapsed < ONE_MIN_FIVE_SECS {
-            log::info!("blocking until next minute");
-            async_std::task::sleep(ONE_MIN_FIVE_SECS - elapsed).await;
+    /// Returns when the first host region is available again if all of them are throttled.
+    fn throttled_until(&self) -> Option<Instant> {
+        let regions = self
+            .hosts
+            .iter()
+            .flat_map(|h| h.regions())
+            .collect::<Vec<_>>();
+        self.limiter.all_disabled_until(&regions)
+    }
+
+    async fn fetch_local(&self, requests: &[shared::Request]) -> Vec<shared::Result<OHLCVList>> {
+        match &self.rpc {
+            Some(rpc) => future::join_all(requests.iter().map(|r| rpc.ohlcv_data(r))).await,
+            None => requests
+                .iter()
+                .map(|_| Err(shared::Error::Config("RPC provider not configured".into())))
+                .collect(),
+        }
+    }
+
+    async fn handle(&mut self, mut pair: shared::Request, resp: shared::Result<Bars>) {
+        match resp {
+            Err(e) => {
+                // TODO: ignore pair after max attempts
+                log::error!(
+                    "failed to get data for pair ({}) {} (network: {}): {}",
+                    pair.token
+                        .as_ref()
+                        .map(|(b, q)| format!("{}/{}", b, q))
+                        .unwrap_or_default(),
+                    pair.pool_address,
+                    pair.network,
+                    e
+                );
+                let code = match &e {
+                    shared::Error::UnexpectedStatusCode(s, _) => s.to_string(),
+                    _ => "none".into(),
+                };
+                metrics::PROVIDER_ERRORS
+                    .with_label_values(&[pair.provider.name(), &code])
+                    .inc();
+                match e {
+                    // try another provider if this one doesn't have the pool or is throttling us
+                    shared::Error::UnexpectedStatusCode(s, _)
+                        if (400..500).contains(&s) && self.fallback(&mut pair) =>
+                    {
+                        log::info!(
+                            "retrying pool {} with provider {:?}",
+                            pair.pool_address,
+                            pair.provider
+                        );
+                        self.queue.push(pair)
+                    }
+                    // pool doesn't exist (better ignore it for the day)
+                    shared::Error::UnexpectedStatusCode(404, _) => (),
+                    _ => self.queue.push(pair),
+                }
+            }
+            Ok(resp) => {
+                let notifier = match &self.watch_notifier {
+                    Some(n) if pair.watched => n,
+                    _ => self.notifier.get(&pair.network).expect("missing notifier"),
+                };
+                if let Err(e) = notifier.post_analysis(&pair, resp).await {
+                    log::error!("failed to post analysis: {}", e);
+                }
+            }
+        }
+    }
+
+    fn supports(&self, provider: shared::ProviderId, req: &shared::Request) -> bool {
+        analyzer::supports(self.rpc.as_deref(), provider, req)
+    }
+
+    /// Switches the request to the next provider (in the configured order) that supports it.
+    fn fallback(&self, req: &mut shared::Request) -> bool {
+        let next = self
+            .config
+            .providers
+            .iter()
+            .skip_while(|&&p| p != req.provider)
+            .skip(1)
+            .find(|&&p| self.supports(p, req))
+            .cloned();
+        match next {
+            Some(p) => {
+                req.provider = p;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Waits for the time between rounds the limiter derived from the last responses.
+    async fn block_until_next_round(&mut self, regions: &[String]) {
+        let interval = self.limiter.round_interval(regions);
+        if let Some(elapsed) = self.current.map(|t| t.elapsed()) {
+            if elapsed < interval {
+                log::info!(
+                    "blocking until next round (every {} seconds)",
+                    interval.as_secs()
+                );
+                async_std::task::sleep(interval - elapsed).await;
+            }
         }
-        self.current = Instant::now();
+        self.current = Some(Instant::now());
     }
 }
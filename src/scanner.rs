@@ -0,0 +1,522 @@
+use futures::future;
+use tokio::sync::mpsc;
+
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::control::{Control, ScanState};
+use super::runner::Config;
+use super::storage::{BlockKind, Storage};
+
+use super::{
+    analyzer,
+    feed::{normalize_address, FeedClient, Network, Pair, PoolId},
+    metrics,
+    provider::RpcSwaps,
+    security::{self, SecurityProvider},
+};
+
+const MAX_PAGE_ATTEMPTS: usize = 5;
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Progress of scanning a network.
+pub struct Scan {
+    pub network: Network,
+    pub page: u16,
+    pub ended_feeds: Vec<bool>,
+    pub pools: HashSet<PoolId>,
+    /// Base token symbols (for flagging duplicates when token addresses are missing)
+    pairs: HashSet<String>,
+    /// Number of pools selected for each base token
+    tokens: HashMap<String, usize>,
+    pub started: Instant,
+    /// Candles analyzed for the pools (matching the interval between scans)
+    pub timeframe: shared::Timeframe,
+}
+
+impl Scan {
+    pub fn new(network: Network, num_feeds: usize, timeframe: shared::Timeframe) -> Self {
+        Scan {
+            network,
+            page: 1,
+            ended_feeds: vec![false; num_feeds],
+            pools: HashSet::with_capacity(1000),
+            pairs: HashSet::with_capacity(1000),
+            tokens: HashMap::with_capacity(1000),
+            started: Instant::now(),
+            timeframe,
+        }
+    }
+
+    fn state(&self) -> ScanState {
+        ScanState {
+            network: self.network.to_string(),
+            page: self.page,
+            ended_feeds: self.ended_feeds.clone(),
+        }
+    }
+}
+
+/// Requests from a page of the feeds.
+pub struct Page {
+    pub network: Network,
+    pub requests: Vec<shared::Request>,
+    /// Feeds of the pools that have already been queued (in earlier pages)
+    pub duplicates: Vec<(String, Vec<(String, usize)>)>,
+    /// Pools skipped in favor of other pools of the same base token (token, pool)
+    pub siblings: Vec<(String, String)>,
+}
+
+/// How a scan ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOutcome {
+    /// All the pages were scanned (up to the max pages)
+    Finished,
+    /// Stopped before finishing, during shutdown
+    Stopped,
+    /// Fetching a page kept failing
+    GaveUp,
+}
+
+pub enum Event {
+    Page(Page),
+    /// The scan has ended
+    Done {
+        scan: Scan,
+        outcome: ScanOutcome,
+    },
+}
+
+/// Fetches the feeds of a network (in its own task) and sends the pools to the runner for dispatching.
+#[derive(Clone)]
+pub struct Scanner {
+    pub(crate) feeds: Vec<Arc<dyn FeedClient + Send + Sync + 'static>>,
+    pub(crate) security: Option<Arc<dyn SecurityProvider + Send + Sync + 'static>>,
+    pub(crate) rpc: Option<Arc<RpcSwaps>>,
+    pub(crate) storage: Storage,
+    pub(crate) control: Control,
+    pub(crate) config: Arc<Config>,
+}
+
+impl Scanner {
+    /// Scans the network page by page until the feeds end, the runner shuts down,
+    /// or fetching a page keeps failing.
+    pub async fn run(self, mut scan: Scan, tx: mpsc::Sender<Event>) {
+        let network = scan.network.to_string();
+        let mut failures = 0;
+        let outcome = loop {
+            self.control.wait_if_paused().await;
+            if self.control.is_shutting_down() {
+                break ScanOutcome::Stopped;
+            }
+            self.control.update_state(|s| {
+                s.scans.retain(|other| other.network != network);
+                s.scans.push(scan.state());
+            });
+
+            let more = match self.populate_pairs(&mut scan).await {
+                Ok((page, more)) => {
+                    failures = 0;
+                    if tx.send(Event::Page(page)).await.is_err() {
+                        break ScanOutcome::Stopped;
+                    }
+                    more
+                }
+                Err(e) => {
+                    failures += 1;
+                    log::error!(
+                        "failed to populate pairs for network {} (attempt {}): {}",
+                        network,
+                        failures,
+                        e
+                    );
+                    if failures >= MAX_PAGE_ATTEMPTS {
+                        log::error!("giving up on network {} at page {}", network, scan.page);
+                        break ScanOutcome::GaveUp;
+                    }
+                    async_std::task::sleep(RETRY_DELAY).await;
+                    continue;
+                }
+            };
+            if !more {
+                break ScanOutcome::Finished;
+            }
+            scan.page += 1;
+            if let Some(max) = self.config.max_pages {
+                if scan.page > max {
+                    log::info!("reached max pages for network: {}", network);
+                    break ScanOutcome::Finished;
+                }
+            }
+        };
+
+        self.control
+            .update_state(|s| s.scans.retain(|other| other.network != network));
+        _ = tx.send(Event::Done { scan, outcome }).await;
+    }
+
+    /// Returns the watched pools of the network, regardless of whether they're in the feeds.
+    pub fn watchlist(&self, scan: &mut Scan) -> Vec<shared::Request> {
+        let network = scan.network;
+        let watchlist = match self.storage.watchlist(Some(&network.to_string())) {
+            Ok(w) => w,
+            Err(e) => {
+                log::error!("failed to load watchlist for network {}: {}", network, e);
+                return vec![];
+            }
+        };
+        let mut requests = vec![];
+        for watched in watchlist {
+            let id = PoolId::new(network, &watched.pool_address);
+            if !scan.pools.insert(id.clone()) {
+                continue;
+            }
+            log::info!("adding watched pool: {} (network: {})", id.address, network);
+            let mut req = shared::Request {
+                network: network.to_string(),
+                pool_address: id.address,
+                watched: true,
+                note: watched.note,
+                timeframe: scan.timeframe,
+                ..Default::default()
+            };
+            req.provider = self.preferred_provider(&req);
+            requests.push(req);
+        }
+        requests
+    }
+
+    /// Fetches the next page of the feeds, returning whether there are more pages.
+    async fn populate_pairs(&self, scan: &mut Scan) -> shared::Result<(Page, bool)> {
+        let network = scan.network;
+        log::info!(
+            "fetching addresses for network: {}, page: {}",
+            network,
+            scan.page
+        );
+        let network_label = network.to_string();
+        let res = future::join_all(
+            self.feeds
+                .iter()
+                .enumerate()
+                .map(|(i, f)| {
+                    let (ended, page) = (scan.ended_feeds[i], scan.page);
+                    async move {
+                        if ended {
+                            return Ok(vec![]);
+                        }
+                        f.fetch_addresses(network, page).await
+                    }
+                })
+                .collect::<Vec<_>>(),
+        )
+        .await;
+
+        // merge the same pool surfaced by multiple feeds before buffering
+        let mut merged: Vec<(PoolId, Pair)> = vec![];
+        let mut index: HashMap<PoolId, usize> = HashMap::new();
+        for (i, pairs) in res.into_iter().enumerate() {
+            let pairs = pairs?;
+            log::debug!("received {} pairs", pairs.len());
+            if pairs.is_empty() {
+                log::info!(
+                    "feed {} has ended for page {} (network: {})",
+                    self.feeds[i].name(),
+                    scan.page,
+                    network
+                );
+                scan.ended_feeds[i] = true;
+            }
+            for pair in pairs {
+                let id = PoolId::new(network, &pair.contract_address);
+                match index.get(&id) {
+                    Some(&j) => merged[j].1.merge(pair),
+                    None => {
+                        index.insert(id.clone(), merged.len());
+                        merged.push((id, pair));
+                    }
+                }
+            }
+        }
+
+        let mut page = Page {
+            network,
+            requests: vec![],
+            duplicates: vec![],
+            siblings: vec![],
+        };
+        let mut candidates = vec![];
+        for (id, mut pair) in merged {
+            match self
+                .storage
+                .is_blocked(&id.network.to_string(), BlockKind::Pool, &id.address)
+            {
+                Err(e) => log::error!("failed to check blocklist for address {}", e),
+                Ok(true) => {
+                    log::info!("skipping blocked address: {}", id.address);
+                    metrics::FILTERED_PAIRS
+                        .with_label_values(&["blocked_pool", &network_label])
+                        .inc();
+                    continue;
+                }
+                Ok(false) => (),
+            }
+
+            pair.base_token_address = normalize_address(network, &pair.base_token_address);
+            if !pair.base_token_address.is_empty() {
+                match self.storage.is_blocked(
+                    &id.network.to_string(),
+                    BlockKind::Token,
+                    &pair.base_token_address,
+                ) {
+                    Err(e) => log::error!("failed to check blocklist for token {}", e),
+                    Ok(true) => {
+                        log::info!(
+                            "skipping pool {} of blocked token: {}",
+                            id.address,
+                            pair.base_token_address
+                        );
+                        metrics::FILTERED_PAIRS
+                            .with_label_values(&["blocked_token", &network_label])
+                            .inc();
+                        continue;
+                    }
+                    Ok(false) => (),
+                }
+            }
+            if let Some(min) = pair.liquidity {
+                if min < self.config.min_liquidity as f64 {
+                    log::info!("skipping low liquidity pool: {} (USD: {})", id.address, min,);
+                    metrics::FILTERED_PAIRS
+                        .with_label_values(&["low_liquidity", &network_label])
+                        .inc();
+                    continue;
+                }
+            }
+
+            if !scan.pools.insert(id.clone()) {
+                log::info!("skipping duplicate address: {}", id.address);
+                metrics::FILTERED_PAIRS
+                    .with_label_values(&["duplicate", &network_label])
+                    .inc();
+                // still record the feeds if the pool hasn't been dispatched yet
+                page.duplicates.push((
+                    id.address,
+                    pair.sources
+                        .iter()
+                        .map(|s| (s.feed.to_string(), s.rank))
+                        .collect(),
+                ));
+                continue;
+            }
+
+            candidates.push((id, pair));
+        }
+
+        let selected = self.select_per_token(&mut scan.tokens, &candidates);
+        for ((id, pair), selected) in candidates.into_iter().zip(selected) {
+            if !selected {
+                metrics::FILTERED_PAIRS
+                    .with_label_values(&["per_token_limit", &network_label])
+                    .inc();
+                log::info!(
+                    "skipping sibling pool {} of token {}",
+                    id.address,
+                    pair.base_token_address
+                );
+                page.siblings.push((pair.base_token_address, id.address));
+                continue;
+            }
+            let base_token_address = pair.base_token_address;
+            let quote_token_address = normalize_address(network, &pair.quote_token_address);
+            let mut req = shared::Request {
+                network: network.to_string(),
+                pool_address: id.address,
+                timeframe: scan.timeframe,
+                mc_or_fdv: pair.mc_or_fdv,
+                // token addresses are reliable, fallback to symbols only when they're missing
+                maybe_duplicate: base_token_address.is_empty()
+                    && !pair.base_token.is_empty()
+                    && !scan.pairs.insert(pair.base_token.clone()),
+                token: if pair.base_token.is_empty() && pair.quote_token.is_empty() {
+                    None
+                } else {
+                    Some((pair.base_token, pair.quote_token))
+                },
+                base_token_address: if base_token_address.is_empty() {
+                    None
+                } else {
+                    Some(base_token_address)
+                },
+                quote_token_address: if quote_token_address.is_empty() {
+                    None
+                } else {
+                    Some(quote_token_address)
+                },
+                sources: pair
+                    .sources
+                    .iter()
+                    .map(|s| (s.feed.to_string(), s.rank))
+                    .collect(),
+                ..Default::default()
+            };
+            req.provider = self.preferred_provider(&req);
+            page.requests.push(req);
+        }
+        page.requests = self.screen(network, page.requests).await;
+
+        if scan.ended_feeds.iter().all(|&e| e) {
+            log::debug!("all feeds ended for network: {}", network);
+            return Ok((page, false));
+        }
+        Ok((page, true))
+    }
+
+    /// Screens the base tokens of pools (if enabled), either dropping risky pools
+    /// or annotating them with risk flags.
+    async fn screen(
+        &self,
+        network: Network,
+        requests: Vec<shared::Request>,
+    ) -> Vec<shared::Request> {
+        let (provider, config) = match (&self.security, &self.config.security) {
+            (Some(p), Some(c)) => (p, c),
+            _ => return requests,
+        };
+        let mut screened = Vec::with_capacity(requests.len());
+        for mut req in requests {
+            let token = match req.base_token_address.clone() {
+                Some(t) => t,
+                None => {
+                    screened.push(req);
+                    continue;
+                }
+            };
+            match security::screen(provider.as_ref(), &self.storage, config, network, &token).await
+            {
+                Err(e) => log::warn!("failed to screen token {}: {}", token, e),
+                Ok(flags) if flags.is_empty() => (),
+                Ok(flags) if config.filter => {
+                    log::info!("skipping risky pool: {} ({:?})", req.pool_address, flags);
+                    metrics::FILTERED_PAIRS
+                        .with_label_values(&["risky", &network.to_string()])
+                        .inc();
+                    continue;
+                }
+                Ok(flags) => req.risk_flags = flags.iter().map(ToString::to_string).collect(),
+            }
+            screened.push(req);
+        }
+        screened
+    }
+
+    /// Picks the deepest-liquidity pools (up to `max_pools_per_token`) for each base token,
+    /// accounting for the pools of that token that have already been selected for this network.
+    fn select_per_token(
+        &self,
+        tokens: &mut HashMap<String, usize>,
+        candidates: &[(PoolId, Pair)],
+    ) -> Vec<bool> {
+        let mut selected = vec![true; candidates.len()];
+        let limit = self.config.max_pools_per_token as usize;
+        if limit == 0 {
+            return selected;
+        }
+
+        let mut by_token: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, (_, pair)) in candidates.iter().enumerate() {
+            if !pair.base_token_address.is_empty() {
+                by_token
+                    .entry(pair.base_token_address.as_str())
+                    .or_default()
+                    .push(i);
+            }
+        }
+
+        for (token, mut idx) in by_token {
+            idx.sort_by(|&a, &b| {
+                let (a, b) = (candidates[a].1.liquidity, candidates[b].1.liquidity);
+                b.unwrap_or(0.)
+                    .partial_cmp(&a.unwrap_or(0.))
+                    .unwrap_or(Ordering::Equal)
+            });
+            let count = tokens.entry(token.into()).or_default();
+            let remaining = limit.saturating_sub(*count);
+            for &i in idx.iter().skip(remaining) {
+                selected[i] = false;
+            }
+            *count += remaining.min(idx.len());
+        }
+        selected
+    }
+
+    /// Returns the first configured provider that supports the request.
+    fn preferred_provider(&self, req: &shared::Request) -> shared::ProviderId {
+        self.config
+            .providers
+            .iter()
+            .find(|&&p| analyzer::supports(self.rpc.as_deref(), p, req))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Control, Network, Pair, PoolId, Scanner, Storage};
+    use serde_json::json;
+
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn pair(address: &str, token: &str, liquidity: Option<f64>) -> (PoolId, Pair) {
+        let pair = Pair {
+            base_token: "FOO".into(),
+            quote_token: "WETH".into(),
+            base_token_address: token.into(),
+            quote_token_address: "0x4200".into(),
+            contract_address: address.into(),
+            mc_or_fdv: Some(1_500_000.),
+            liquidity,
+            sources: vec![],
+        };
+        (PoolId::new(Network::Base, address), pair)
+    }
+
+    #[test]
+    fn select_deepest_pool_per_token() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scanner.redb");
+        let scanner = Scanner {
+            feeds: vec![],
+            security: None,
+            rpc: None,
+            storage: Storage::new(path.to_str().unwrap()).unwrap(),
+            control: Control::default(),
+            config: Arc::new(
+                serde_json::from_value(json!({
+                    "lambda_function": "test",
+                    "storage_path": path,
+                    "host_requests_per_min": 30,
+                    "max_pools_per_token": 1,
+                }))
+                .unwrap(),
+            ),
+        };
+
+        let mut tokens = HashMap::new();
+        let candidates = [
+            pair("0xa", "0xf00", Some(1200.5)),
+            pair("0xb", "0xf00", Some(98000.)),
+            pair("0xc", "0xba5", None),
+        ];
+        let selected = scanner.select_per_token(&mut tokens, &candidates);
+        assert_eq!(selected, vec![false, true, true]);
+
+        // the token's quota has been used up by the earlier page
+        let selected = scanner.select_per_token(&mut tokens, &[pair("0xd", "0xf00", Some(1e6))]);
+        assert_eq!(selected, vec![false]);
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::{offset::Utc, DateTime};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use shared::Timeframe;
+
+use std::str::FromStr;
+
+/// Default cycle (every day at 00:01 UTC, right after the daily candle closes).
+const DAILY: &str = "0 1 0 * * *";
+
+/// Number of upcoming runs checked for the shortest interval.
+const CHECKED_RUNS: usize = 400;
+
+/// Slack for the intervals shortened by DST changes.
+const DST_SLACK_SECS: i64 = 3600;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Cron expression with seconds (`sec min hour day month weekday [year]`)
+    pub cron: String,
+    /// IANA timezone in which the expression is evaluated (UTC if unset)
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// When a network should be scanned.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    cron: cron::Schedule,
+    tz: Tz,
+    timeframe: Timeframe,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            cron: cron::Schedule::from_str(DAILY).expect("invalid default schedule"),
+            tz: Tz::UTC,
+            timeframe: Timeframe::Day,
+        }
+    }
+}
+
+impl Schedule {
+    pub fn new(config: &Config) -> shared::Result<Self> {
+        let cron = cron::Schedule::from_str(&config.cron).map_err(|e| {
+            shared::Error::Config(format!("invalid cron expression {}: {}", config.cron, e))
+        })?;
+        let tz = match &config.timezone {
+            Some(tz) => Tz::from_str(tz)
+                .map_err(|_| shared::Error::Config(format!("invalid timezone: {}", tz)))?,
+            None => Tz::UTC,
+        };
+        // a run analyzes the candles closed since the previous one, so more frequent runs
+        // need shorter candles (or they'd alert on the same daily candle again)
+        let shortest = cron
+            .upcoming(tz)
+            .take(CHECKED_RUNS)
+            .collect::<Vec<_>>()
+            .windows(2)
+            .map(|w| (w[1] - w[0]).num_seconds())
+            .min();
+        let timeframe = match shortest {
+            Some(secs) => Timeframe::VARIANTS
+                .into_iter()
+                .find(|tf| tf.secs() as i64 <= secs + DST_SLACK_SECS)
+                .unwrap_or(Timeframe::Hour),
+            None => Timeframe::Day,
+        };
+        Ok(Schedule {
+            cron,
+            tz,
+            timeframe,
+        })
+    }
+
+    /// Returns the candle timeframe matching the interval between runs.
+    pub fn timeframe(&self) -> Timeframe {
+        self.timeframe
+    }
+
+    /// Returns the first scheduled time after the given time.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.cron
+            .after(&time.with_timezone(&self.tz))
+            .next()
+            .map(|t| t.with_timezone(&Utc))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, Schedule};
+    use chrono::{offset::Utc, TimeZone};
+    use shared::Timeframe;
+
+    #[test]
+    fn next_runs() {
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 13, 30, 0).unwrap();
+        assert_eq!(
+            Schedule::default().next_after(now),
+            Some(Utc.with_ymd_and_hms(2024, 7, 2, 0, 1, 0).unwrap())
+        );
+
+        let four_hourly = Schedule::new(&Config {
+            cron: "0 1 0/4 * * *".into(),
+            timezone: None,
+        })
+        .unwrap();
+        assert_eq!(
+            four_hourly.next_after(now),
+            Some(Utc.with_ymd_and_hms(2024, 7, 1, 16, 1, 0).unwrap())
+        );
+
+        // daily close of US exchanges (EDT in July)
+        let ny_close = Schedule::new(&Config {
+            cron: "0 5 16 * * Mon-Fri".into(),
+            timezone: Some("America/New_York".into()),
+        })
+        .unwrap();
+        assert_eq!(
+            ny_close.next_after(now),
+            Some(Utc.with_ymd_and_hms(2024, 7, 1, 20, 5, 0).unwrap())
+        );
+
+        assert!(Schedule::new(&Config {
+            cron: "0 1 0 * * *".into(),
+            timezone: Some("Mars/Olympus".into()),
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn timeframes() {
+        let timeframe = |cron: &str, tz: Option<&str>| {
+            Schedule::new(&Config {
+                cron: cron.into(),
+                timezone: tz.map(Into::into),
+            })
+            .unwrap()
+            .timeframe()
+        };
+        assert_eq!(Schedule::default().timeframe(), Timeframe::Day);
+        assert_eq!(timeframe("0 1 0 */2 * *", None), Timeframe::Day);
+        // 23 hours between the runs around the DST change
+        assert_eq!(
+            timeframe("0 5 16 * * Mon-Fri", Some("America/New_York")),
+            Timeframe::Day
+        );
+        assert_eq!(timeframe("0 1 0/4 * * *", None), Timeframe::FourHours);
+        assert_eq!(timeframe("0 1 0/6 * * *", None), Timeframe::FourHours);
+        assert_eq!(timeframe("0 1 * * * *", None), Timeframe::Hour);
+        assert_eq!(timeframe("0 */15 * * * *", None), Timeframe::Hour);
+    }
+}
@@ -0,0 +1,260 @@
+use async_std::sync::Mutex;
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::TokenSecurity;
+use crate::feed::Network;
+
+// unauthenticated API allows ~30 requests per minute
+const DELAY: Duration = Duration::from_millis(2100);
+const DEAD_ADDRESSES: [&str; 2] = [
+    "0x0000000000000000000000000000000000000000",
+    "0x000000000000000000000000000000000000dead",
+];
+
+pub struct GoPlus {
+    client: reqwest::Client,
+    last_request_time: Mutex<Instant>,
+}
+
+impl Default for GoPlus {
+    fn default() -> Self {
+        GoPlus {
+            client: reqwest::Client::new(),
+            last_request_time: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(bound(deserialize = "T: Deserialize<'de>"))]
+struct Resp<T> {
+    code: i64,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    result: HashMap<String, T>,
+}
+
+#[derive(Deserialize)]
+struct Holder {
+    #[serde(default)]
+    address: String,
+    #[serde(default)]
+    percent: String,
+    #[serde(default)]
+    is_locked: i64,
+    #[serde(default)]
+    is_contract: i64,
+}
+
+#[derive(Deserialize)]
+struct EvmToken {
+    #[serde(default)]
+    is_honeypot: String,
+    #[serde(default)]
+    is_mintable: String,
+    #[serde(default)]
+    buy_tax: String,
+    #[serde(default)]
+    sell_tax: String,
+    #[serde(default)]
+    holders: Vec<Holder>,
+    #[serde(default)]
+    lp_holders: Vec<Holder>,
+}
+
+#[derive(Deserialize)]
+struct Status {
+    status: String,
+}
+
+#[derive(Deserialize)]
+struct SolanaToken {
+    #[serde(default)]
+    mintable: Option<Status>,
+    #[serde(default)]
+    freezable: Option<Status>,
+    #[serde(default)]
+    holders: Vec<Holder>,
+}
+
+fn flag(s: &str) -> Option<bool> {
+    match s {
+        "1" => Some(true),
+        "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn top_holders(holders: &[Holder]) -> Option<f64> {
+    if holders.is_empty() {
+        return None;
+    }
+    Some(
+        holders
+            .iter()
+            .filter(|h| h.is_locked != 1 && h.is_contract != 1)
+            .take(10)
+            .filter_map(|h| h.percent.parse::<f64>().ok())
+            .sum(),
+    )
+}
+
+impl From<EvmToken> for TokenSecurity {
+    fn from(t: EvmToken) -> Self {
+        TokenSecurity {
+            honeypot: flag(&t.is_honeypot),
+            mintable: flag(&t.is_mintable),
+            freezable: None,
+            lp_locked: if t.lp_holders.is_empty() {
+                None
+            } else {
+                Some(
+                    t.lp_holders
+                        .iter()
+                        .filter(|h| {
+                            h.is_locked == 1 || DEAD_ADDRESSES.contains(&h.address.as_str())
+                        })
+                        .filter_map(|h| h.percent.parse::<f64>().ok())
+                        .sum(),
+                )
+            },
+            top_holders: top_holders(&t.holders),
+            buy_tax: t.buy_tax.parse().ok(),
+            sell_tax: t.sell_tax.parse().ok(),
+        }
+    }
+}
+
+impl From<SolanaToken> for TokenSecurity {
+    fn from(t: SolanaToken) -> Self {
+        TokenSecurity {
+            mintable: t.mintable.and_then(|s| flag(&s.status)),
+            freezable: t.freezable.and_then(|s| flag(&s.status)),
+            top_holders: top_holders(&t.holders),
+            ..Default::default()
+        }
+    }
+}
+
+fn chain_id(network: Network) -> Option<&'static str> {
+    match network {
+        Network::Ethereum => Some("1"),
+        Network::BSC => Some("56"),
+        Network::Arbitrum => Some("42161"),
+        Network::Base => Some("8453"),
+        Network::Avalanche => Some("43114"),
+        Network::Optimism => Some("10"),
+        Network::Fantom => Some("250"),
+        Network::Blast => Some("81457"),
+        _ => None,
+    }
+}
+
+impl GoPlus {
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        url: String,
+        token: &str,
+    ) -> shared::Result<Option<T>> {
+        {
+            let mut time = self.last_request_time.lock().await;
+            let elapsed = time.elapsed();
+            if elapsed < DELAY {
+                async_std::task::sleep(DELAY - elapsed).await;
+            }
+            *time = Instant::now();
+        }
+
+        log::info!("GET {}", url);
+        let resp = self.client.get(&url).send().await?;
+        let code = resp.status();
+        let bytes = resp.bytes().await?;
+        if !code.is_success() {
+            return Err(shared::Error::UnexpectedStatusCode(
+                code.as_u16(),
+                Some(String::from_utf8_lossy(&bytes).to_string()),
+            ));
+        }
+        let r: Resp<T> = serde_json::from_slice(&bytes).map_err(|_| {
+            shared::Error::UnexpectedResponse(String::from_utf8_lossy(&bytes).to_string())
+        })?;
+        Self::extract(r, token)
+    }
+
+    fn extract<T>(mut r: Resp<T>, token: &str) -> shared::Result<Option<T>> {
+        if r.code != 1 {
+            return Err(shared::Error::UnexpectedResponse(r.message));
+        }
+        // EVM results are keyed by the lowercase address
+        Ok(r.result
+            .remove(token)
+            .or_else(|| r.result.remove(&token.to_lowercase())))
+    }
+}
+
+#[async_trait::async_trait]
+impl super::SecurityProvider for GoPlus {
+    async fn token_security(
+        &self,
+        network: Network,
+        token: &str,
+    ) -> shared::Result<Option<TokenSecurity>> {
+        if let Network::Solana = network {
+            let url = format!(
+                "https://api.gopluslabs.io/api/v1/solana/token_security?contract_addresses={}",
+                token
+            );
+            return Ok(self.get::<SolanaToken>(url, token).await?.map(Into::into));
+        }
+        let chain = match chain_id(network) {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        let url = format!(
+            "https://api.gopluslabs.io/api/v1/token_security/{}?contract_addresses={}",
+            chain, token
+        );
+        Ok(self.get::<EvmToken>(url, token).await?.map(Into::into))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EvmToken, GoPlus, Resp, SolanaToken};
+    use crate::security::TokenSecurity;
+    use serde_json::json;
+
+    #[test]
+    fn extract_evm() {
+        let data: Resp<EvmToken> = serde_json::from_value(json!({"code":1,"message":"OK","result":{"0x57b96d4af698605563a4653d882635da59bf11af":{"buy_tax":"0","sell_tax":"0.05","is_honeypot":"0","is_mintable":"1","is_open_source":"1","holder_count":"2156","holders":[{"address":"0xc39e83fe4e412a885c0577c08eb53bdb6548004a","tag":"UniswapV3","is_contract":1,"balance":"11612","percent":"0.4612","is_locked":0},{"address":"0x18709e89bd403f470088abdacebe86cc60dda12e","tag":"","is_contract":0,"balance":"1200","percent":"0.12","is_locked":0},{"address":"0x663a5c229c09b049e36dcc11a9b0d4a8eb9db214","tag":"UNCX","is_contract":1,"balance":"900","percent":"0.09","is_locked":1},{"address":"0x2f3a0b8c5a5e9c2b1bd7e3d8c9f1a6e5b4c3d2e1","tag":"","is_contract":0,"balance":"310","percent":"0.031","is_locked":0}],"lp_holders":[{"address":"0x000000000000000000000000000000000000dead","tag":"Null Address","is_contract":0,"balance":"10","percent":"0.75","is_locked":0},{"address":"0x663a5c229c09b049e36dcc11a9b0d4a8eb9db214","tag":"UNCX","is_contract":1,"balance":"2","percent":"0.15","is_locked":1},{"address":"0x18709e89bd403f470088abdacebe86cc60dda12e","tag":"","is_contract":0,"balance":"1","percent":"0.1","is_locked":0}]}}})).unwrap();
+        let security: TokenSecurity =
+            GoPlus::extract(data, "0x57B96D4af698605563A4653D882635da59Bf11AF")
+                .unwrap()
+                .expect("missing token")
+                .into();
+        assert_eq!(security.honeypot, Some(false));
+        assert_eq!(security.mintable, Some(true));
+        assert_eq!(security.buy_tax, Some(0.));
+        assert_eq!(security.sell_tax, Some(0.05));
+        assert!((security.lp_locked.unwrap() - 0.9).abs() < 1e-9);
+        assert!((security.top_holders.unwrap() - 0.151).abs() < 1e-9);
+    }
+
+    #[test]
+    fn extract_solana() {
+        let data: Resp<SolanaToken> = serde_json::from_value(json!({"code":1,"message":"OK","result":{"C1XRDFrD9EYNUvi69SD9tZs4nvq3oVWNACUfFHpwWu3y":{"mintable":{"status":"0","authority":[]},"freezable":{"status":"1","authority":[{"address":"5Q544fKrFoe6tsEbD7S8EmxGTJYAKtTVhAW5Q5pge4j1","malicious_address":0}]},"holders":[{"account":"BQ72nSv9f3PRyRKCBnHLVrerrv37CYTHm5h3s9VSGQDV","address":"BQ72nSv9f3PRyRKCBnHLVrerrv37CYTHm5h3s9VSGQDV","balance":"1000","percent":"0.62","is_locked":0},{"account":"3HbK8Kv3JtJVj5t3rXbKvRm5vGyYBtK4G2xZ1hW8sPfN","address":"3HbK8Kv3JtJVj5t3rXbKvRm5vGyYBtK4G2xZ1hW8sPfN","balance":"100","percent":"0.05","is_locked":0}]}}})).unwrap();
+        let security: TokenSecurity =
+            GoPlus::extract(data, "C1XRDFrD9EYNUvi69SD9tZs4nvq3oVWNACUfFHpwWu3y")
+                .unwrap()
+                .expect("missing token")
+                .into();
+        assert_eq!(security.mintable, Some(false));
+        assert_eq!(security.freezable, Some(true));
+        assert!((security.top_holders.unwrap() - 0.67).abs() < 1e-9);
+        assert_eq!(security.lp_locked, None);
+    }
+}
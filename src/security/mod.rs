@@ -0,0 +1,231 @@
+use chrono::{offset::Utc, DateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use super::feed::Network;
+use super::storage::Storage;
+
+mod goplus;
+
+pub use self::goplus::GoPlus;
+
+const fn default_max_tax() -> f64 {
+    0.1
+}
+const fn default_min_lp_locked() -> f64 {
+    0.9
+}
+const fn default_max_top_holders() -> f64 {
+    0.5
+}
+const fn default_cache_hours() -> i64 {
+    24
+}
+
+#[derive(Deserialize)]
+pub struct Config {
+    /// Drop flagged pools instead of annotating their alerts
+    #[serde(default)]
+    pub filter: bool,
+    #[serde(default = "default_max_tax")]
+    pub max_tax: f64,
+    #[serde(default = "default_min_lp_locked")]
+    pub min_lp_locked: f64,
+    #[serde(default = "default_max_top_holders")]
+    pub max_top_holders: f64,
+    #[serde(default = "default_cache_hours")]
+    pub cache_hours: i64,
+}
+
+#[async_trait::async_trait]
+pub trait SecurityProvider {
+    /// Returns `None` if the network or token isn't supported by the provider.
+    async fn token_security(
+        &self,
+        network: Network,
+        token: &str,
+    ) -> shared::Result<Option<TokenSecurity>>;
+}
+
+/// Security attributes of a token (fractions are in `0..1`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenSecurity {
+    pub honeypot: Option<bool>,
+    pub mintable: Option<bool>,
+    pub freezable: Option<bool>,
+    /// Fraction of LP tokens that are locked or burnt
+    pub lp_locked: Option<f64>,
+    /// Fraction of supply held by the top 10 (non-contract) holders
+    pub top_holders: Option<f64>,
+    pub buy_tax: Option<f64>,
+    pub sell_tax: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cached {
+    pub checked_at: DateTime<Utc>,
+    pub security: TokenSecurity,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RiskFlag {
+    Honeypot,
+    Mintable,
+    Freezable,
+    UnlockedLiquidity(f64),
+    HolderConcentration(f64),
+    BuyTax(f64),
+    SellTax(f64),
+}
+
+impl std::fmt::Display for RiskFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskFlag::Honeypot => write!(f, "honeypot"),
+            RiskFlag::Mintable => write!(f, "mintable"),
+            RiskFlag::Freezable => write!(f, "freezable"),
+            RiskFlag::UnlockedLiquidity(p) => write!(f, "LP locked {:.0}%", p * 100.),
+            RiskFlag::HolderConcentration(p) => write!(f, "top holders {:.0}%", p * 100.),
+            RiskFlag::BuyTax(p) => write!(f, "buy tax {:.0}%", p * 100.),
+            RiskFlag::SellTax(p) => write!(f, "sell tax {:.0}%", p * 100.),
+        }
+    }
+}
+
+impl TokenSecurity {
+    pub fn risk_flags(&self, config: &Config) -> Vec<RiskFlag> {
+        let mut flags = vec![];
+        if self.honeypot == Some(true) {
+            flags.push(RiskFlag::Honeypot);
+        }
+        if self.mintable == Some(true) {
+            flags.push(RiskFlag::Mintable);
+        }
+        if self.freezable == Some(true) {
+            flags.push(RiskFlag::Freezable);
+        }
+        match self.lp_locked {
+            Some(p) if p < config.min_lp_locked => flags.push(RiskFlag::UnlockedLiquidity(p)),
+            _ => (),
+        }
+        match self.top_holders {
+            Some(p) if p > config.max_top_holders => flags.push(RiskFlag::HolderConcentration(p)),
+            _ => (),
+        }
+        match self.buy_tax {
+            Some(p) if p > config.max_tax => flags.push(RiskFlag::BuyTax(p)),
+            _ => (),
+        }
+        match self.sell_tax {
+            Some(p) if p > config.max_tax => flags.push(RiskFlag::SellTax(p)),
+            _ => (),
+        }
+        flags
+    }
+}
+
+/// Screens the token, using the cached result from storage if it's still fresh.
+pub async fn screen<S>(
+    provider: &S,
+    storage: &Storage,
+    config: &Config,
+    network: Network,
+    token: &str,
+) -> shared::Result<Vec<RiskFlag>>
+where
+    S: SecurityProvider + Send + Sync + ?Sized,
+{
+    let key = format!("{}_{}", network, token);
+    if let Some(cached) = storage.token_security(&key)? {
+        if Utc::now() - cached.checked_at < TimeDelta::hours(config.cache_hours) {
+            log::debug!("using cached security info for {}", key);
+            return Ok(cached.security.risk_flags(config));
+        }
+    }
+
+    let security = match provider.token_security(network, token).await? {
+        Some(s) => s,
+        None => return Ok(vec![]),
+    };
+    storage.cache_token_security(
+        &key,
+        &Cached {
+            checked_at: Utc::now(),
+            security: security.clone(),
+        },
+    )?;
+    Ok(security.risk_flags(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, RiskFlag, SecurityProvider, TokenSecurity};
+    use crate::feed::Network;
+    use crate::storage::Storage;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct Mock {
+        calls: AtomicUsize,
+        security: TokenSecurity,
+    }
+
+    #[async_trait::async_trait]
+    impl SecurityProvider for Mock {
+        async fn token_security(
+            &self,
+            _network: Network,
+            _token: &str,
+        ) -> shared::Result<Option<TokenSecurity>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Some(self.security.clone()))
+        }
+    }
+
+    fn config() -> Config {
+        serde_json::from_str("{}").unwrap()
+    }
+
+    #[test]
+    fn flag_risks() {
+        let security = TokenSecurity {
+            honeypot: Some(false),
+            mintable: Some(true),
+            freezable: None,
+            lp_locked: Some(0.2),
+            top_holders: Some(0.3),
+            buy_tax: Some(0.),
+            sell_tax: Some(0.25),
+        };
+        let flags = security.risk_flags(&config());
+        assert_eq!(
+            flags,
+            vec![
+                RiskFlag::Mintable,
+                RiskFlag::UnlockedLiquidity(0.2),
+                RiskFlag::SellTax(0.25)
+            ]
+        );
+        assert_eq!(flags[2].to_string(), "sell tax 25%");
+    }
+
+    #[tokio::test]
+    async fn screen_with_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("security.redb");
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        let mock = Mock {
+            calls: AtomicUsize::new(0),
+            security: TokenSecurity {
+                honeypot: Some(true),
+                ..Default::default()
+            },
+        };
+        for _ in 0..2 {
+            let flags = super::screen(&mock, &storage, &config(), Network::Base, "0xabc")
+                .await
+                .unwrap();
+            assert_eq!(flags, vec![RiskFlag::Honeypot]);
+        }
+        assert_eq!(mock.calls.load(Ordering::SeqCst), 1);
+    }
+}
@@ -1,8 +1,191 @@
 use std::sync::Arc;
 
-use redb::{Database, TableDefinition};
+use chrono::{offset::Utc, DateTime};
+use redb::{Database, ReadableTable, ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
 
+use strum::VariantArray;
+
+use super::feed::{normalize_address, normalize_unscoped_address, Network};
+use super::security::Cached;
+
+/// Superseded by `BLOCKLIST` (only read during migration).
 const IGNORED_ADDRS: TableDefinition<&str, ()> = TableDefinition::new("blacklist");
+const BLOCKLIST: TableDefinition<&str, &str> = TableDefinition::new("blocklist");
+/// Addresses from `ignored_pools.txt` which have been added to the blocklist.
+const BLOCKLIST_SEED: TableDefinition<&str, ()> = TableDefinition::new("blocklist_seed");
+const META: TableDefinition<&str, u64> = TableDefinition::new("meta");
+const RUNNER_STATE: TableDefinition<&str, &str> = TableDefinition::new("runner_state");
+const TOKEN_SECURITY: TableDefinition<&str, &str> = TableDefinition::new("token_security");
+const WATCHLIST: TableDefinition<&str, &str> = TableDefinition::new("watchlist");
+
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const SCHEMA_VERSION: u64 = 3;
+const CHECKPOINT_KEY: &str = "checkpoint";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    #[default]
+    Pool,
+    Token,
+}
+
+/// Blocked pool or (base) token address.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blocked {
+    pub address: String,
+    #[serde(default)]
+    pub kind: BlockKind,
+    /// Network in which the address is blocked (all networks if unset)
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub added_by: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub added_at: DateTime<Utc>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Blocked {
+    pub fn new(kind: BlockKind, address: &str) -> Self {
+        Blocked {
+            address: address.into(),
+            kind,
+            network: None,
+            reason: None,
+            added_by: None,
+            added_at: Utc::now(),
+            expires_at: None,
+        }
+    }
+
+    /// Key of the entry for the address in the network (or in all networks if unset).
+    fn key(kind: BlockKind, network: Option<&str>, address: &str) -> String {
+        let scope = network.unwrap_or("*");
+        match kind {
+            BlockKind::Pool => format!("pool_{}_{}", scope, address),
+            BlockKind::Token => format!("token_{}_{}", scope, address),
+        }
+    }
+
+    fn entry_key(&self) -> String {
+        Blocked::key(self.kind, self.network.as_deref(), &self.address)
+    }
+
+    /// Normalizes the address the same way as the lookups (and the single blocks) do.
+    fn normalized(mut self) -> Self {
+        let network = self
+            .network
+            .as_ref()
+            .and_then(|n| Network::VARIANTS.iter().find(|v| v.to_string() == *n));
+        self.address = match network {
+            Some(&n) => normalize_address(n, &self.address),
+            None => normalize_unscoped_address(&self.address),
+        };
+        self
+    }
+
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.map(|t| t <= now).unwrap_or(false)
+    }
+
+    fn applies_to(&self, network: &str) -> bool {
+        self.network.as_ref().map(|n| n == network).unwrap_or(true)
+    }
+}
+
+/// Runner state saved on shutdown (and restored on the next start).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub saved_at: DateTime<Utc>,
+    /// Networks being scanned
+    pub scans: Vec<ScanCheckpoint>,
+    pub buffer: Vec<PendingRequest>,
+}
+
+/// Queued request along with the fields which aren't sent to the workers.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingRequest {
+    #[serde(flatten)]
+    request: shared::Request,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mc_or_fdv: Option<f64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    maybe_duplicate: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    quote_token_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    sources: Vec<(String, usize)>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    siblings: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    risk_flags: Vec<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    watched: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+impl From<shared::Request> for PendingRequest {
+    fn from(mut r: shared::Request) -> Self {
+        PendingRequest {
+            mc_or_fdv: r.mc_or_fdv,
+            maybe_duplicate: r.maybe_duplicate,
+            quote_token_address: r.quote_token_address.take(),
+            sources: std::mem::take(&mut r.sources),
+            siblings: std::mem::take(&mut r.siblings),
+            risk_flags: std::mem::take(&mut r.risk_flags),
+            watched: r.watched,
+            note: r.note.take(),
+            request: r,
+        }
+    }
+}
+
+impl From<PendingRequest> for shared::Request {
+    fn from(p: PendingRequest) -> Self {
+        shared::Request {
+            mc_or_fdv: p.mc_or_fdv,
+            maybe_duplicate: p.maybe_duplicate,
+            quote_token_address: p.quote_token_address,
+            sources: p.sources,
+            siblings: p.siblings,
+            risk_flags: p.risk_flags,
+            watched: p.watched,
+            note: p.note,
+            ..p.request
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanCheckpoint {
+    pub network: String,
+    pub page: u16,
+    pub ended_feeds: Vec<bool>,
+    /// Pools of the network which have already been seen
+    pub pools: Vec<String>,
+}
+
+/// Pool that's analyzed every cycle regardless of the feeds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Watched {
+    pub network: String,
+    pub pool_address: String,
+    #[serde(default)]
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+impl Watched {
+    fn key(network: &str, pool_address: &str) -> String {
+        format!("{}_{}", network, pool_address)
+    }
+}
 
 #[derive(Clone)]
 pub struct Storage {
@@ -14,46 +197,464 @@ impl Storage {
         let s = Storage {
             db: Arc::new(Database::create(path)?),
         };
-        for addr in shared::IGNORED_POOLS {
-            log::info!("adding pool {} to blacklist", addr);
-            s.block_address(addr)?;
+        {
+            // ensure that tables exist for read transactions
+            let tx = s.db.begin_write()?;
+            tx.open_table(BLOCKLIST)?;
+            tx.open_table(BLOCKLIST_SEED)?;
+            tx.open_table(TOKEN_SECURITY)?;
+            tx.open_table(WATCHLIST)?;
+            tx.open_table(META)?;
+            tx.open_table(RUNNER_STATE)?;
+            tx.commit()?;
         }
+        s.migrate()?;
+        s.seed(&shared::IGNORED_POOLS)?;
         Ok(s)
     }
 
+    /// Blocks the pools (in all networks) which haven't been seeded before, so that
+    /// the pools unblocked since then stay unblocked.
+    fn seed(&self, pools: &[&str]) -> shared::Result<()> {
+        let pending = {
+            let tx = self.db.begin_read()?;
+            let seeded = tx.open_table(BLOCKLIST_SEED)?;
+            let mut pending = vec![];
+            for addr in pools {
+                let addr = normalize_unscoped_address(addr);
+                if seeded.get(addr.as_str())?.is_none() {
+                    pending.push(addr);
+                }
+            }
+            pending
+        };
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(BLOCKLIST)?;
+            let mut seeded = tx.open_table(BLOCKLIST_SEED)?;
+            for addr in pending {
+                log::info!("adding pool {} to blocklist", addr);
+                let mut b = Blocked::new(BlockKind::Pool, &addr);
+                b.added_by = Some("ignored_pools.txt".into());
+                let value = serde_json::to_string(&b)?;
+                table.insert(b.entry_key().as_str(), value.as_str())?;
+                seeded.insert(addr.as_str(), ())?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Applies the pending schema migrations (in a single transaction).
+    fn migrate(&self) -> shared::Result<()> {
+        let version = {
+            let tx = self.db.begin_read()?;
+            let meta = tx.open_table(META)?;
+            let version = meta.get(SCHEMA_VERSION_KEY)?.map(|v| v.value());
+            version.unwrap_or(0)
+        };
+        if version >= SCHEMA_VERSION {
+            return Ok(());
+        }
+
+        let tx = self.db.begin_write()?;
+        {
+            if version < 1 {
+                // v1: move the old blacklist into the blocklist
+                let mut entries = vec![];
+                {
+                    let old = tx.open_table(IGNORED_ADDRS)?;
+                    for res in old.iter()? {
+                        let addr = normalize_unscoped_address(res?.0.value());
+                        let mut b = Blocked::new(BlockKind::Pool, &addr);
+                        b.reason = Some("migrated from blacklist".into());
+                        entries.push(b);
+                    }
+                }
+                tx.delete_table(IGNORED_ADDRS)?;
+                let mut table = tx.open_table(BLOCKLIST)?;
+                for b in entries {
+                    log::info!("adding pool {} to blocklist", b.address);
+                    let value = serde_json::to_string(&b)?;
+                    table.insert(b.entry_key().as_str(), value.as_str())?;
+                }
+            }
+
+            if version < 2 {
+                // v2: normalize the addresses blocked in all networks before they
+                // were normalized on insert (lookups use the normalized addresses)
+                let mut table = tx.open_table(BLOCKLIST)?;
+                let mut entries = vec![];
+                for res in table.iter()? {
+                    let (k, v) = res?;
+                    let b: Blocked = serde_json::from_str(v.value())?;
+                    let addr = normalize_unscoped_address(&b.address);
+                    if b.network.is_none() && addr != b.address {
+                        entries.push((k.value().to_string(), Blocked { address: addr, ..b }));
+                    }
+                }
+                for (k, b) in entries {
+                    log::info!("normalizing blocked address {} to {}", k, b.address);
+                    table.remove(k.as_str())?;
+                    let value = serde_json::to_string(&b)?;
+                    table.insert(b.entry_key().as_str(), value.as_str())?;
+                }
+            }
+
+            if version < 3 {
+                // v3: key the entries by their network as well, so that the entries of the
+                // same address in different networks don't overwrite each other
+                let mut table = tx.open_table(BLOCKLIST)?;
+                let mut entries = vec![];
+                for res in table.iter()? {
+                    let (k, v) = res?;
+                    let b: Blocked = serde_json::from_str(v.value())?;
+                    entries.push((k.value().to_string(), b));
+                }
+                for (k, b) in entries {
+                    table.remove(k.as_str())?;
+                    let value = serde_json::to_string(&b)?;
+                    table.insert(b.entry_key().as_str(), value.as_str())?;
+                }
+                if version >= 1 {
+                    // the ignored pools were seeded by v1 (from the same list, presumably)
+                    let mut seeded = tx.open_table(BLOCKLIST_SEED)?;
+                    for addr in shared::IGNORED_POOLS {
+                        seeded.insert(normalize_unscoped_address(addr).as_str(), ())?;
+                    }
+                }
+            }
+
+            let mut meta = tx.open_table(META)?;
+            meta.insert(SCHEMA_VERSION_KEY, SCHEMA_VERSION)?;
+        }
+        tx.commit()?;
+        log::info!("migrated storage to schema version {}", SCHEMA_VERSION);
+        Ok(())
+    }
+
+    /// Checks whether the database is readable.
+    pub fn ping(&self) -> shared::Result<()> {
+        let tx = self.db.begin_read()?;
+        tx.open_table(META)?;
+        Ok(())
+    }
+
     // TODO: redb has its own concurrency management, evaluate whether
     // it's blocking these methods
 
-    pub fn is_blocked(&self, contract_addr: &str) -> shared::Result<bool> {
-        let exists;
+    /// Checks whether the address is blocked for the network or in all networks
+    /// (ignoring expired entries).
+    pub fn is_blocked(&self, network: &str, kind: BlockKind, addr: &str) -> shared::Result<bool> {
         let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKLIST)?;
+        let now = Utc::now();
+        for scope in [None, Some(network)] {
+            if let Some(v) = table.get(Blocked::key(kind, scope, addr).as_str())? {
+                let b: Blocked = serde_json::from_str(v.value())?;
+                if b.applies_to(network) && !b.is_expired(now) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn block(&self, blocked: &Blocked) -> shared::Result<()> {
+        self.block_all(std::slice::from_ref(blocked))
+    }
+
+    /// Blocks all the addresses in a single transaction.
+    pub fn block_all(&self, blocked: &[Blocked]) -> shared::Result<()> {
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(BLOCKLIST)?;
+            for b in blocked {
+                let b = b.clone().normalized();
+                let value = serde_json::to_string(&b)?;
+                table.insert(b.entry_key().as_str(), value.as_str())?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns the total number of blocked addresses and a page of them
+    /// (sorted by kind, network and address).
+    pub fn blocked(&self, offset: usize, limit: usize) -> shared::Result<(u64, Vec<Blocked>)> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKLIST)?;
+        let mut entries = vec![];
+        for res in table.range::<&str>(..)?.skip(offset).take(limit) {
+            entries.push(serde_json::from_str(res?.1.value())?);
+        }
+        Ok((table.len()?, entries))
+    }
+
+    /// Removes the entry of the address in the network (or the one in all networks if unset).
+    pub fn unblock(
+        &self,
+        kind: BlockKind,
+        network: Option<&str>,
+        addr: &str,
+    ) -> shared::Result<bool> {
+        let exists;
+        let tx = self.db.begin_write()?;
         {
-            let table = tx.open_table(IGNORED_ADDRS)?;
-            let res = table.get(contract_addr)?;
+            let mut table = tx.open_table(BLOCKLIST)?;
+            let res = table.remove(Blocked::key(kind, network, addr).as_str())?;
             exists = res.is_some();
         }
+        tx.commit()?;
         Ok(exists)
     }
 
-    pub fn block_address(&self, contract_addr: &str) -> shared::Result<()> {
+    /// Removes the expired entries from the blocklist and returns the number of entries left.
+    pub fn purge_expired_blocks(&self) -> shared::Result<u64> {
+        let now = Utc::now();
         let tx = self.db.begin_write()?;
+        let len;
         {
-            let mut table = tx.open_table(IGNORED_ADDRS)?;
-            table.insert(contract_addr, ())?;
+            let mut table = tx.open_table(BLOCKLIST)?;
+            table.retain(|_, v| {
+                serde_json::from_str::<Blocked>(v)
+                    .map(|b| !b.is_expired(now))
+                    .unwrap_or(true)
+            })?;
+            len = table.len()?;
+        }
+        tx.commit()?;
+        Ok(len)
+    }
+
+    pub fn token_security(&self, key: &str) -> shared::Result<Option<Cached>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(TOKEN_SECURITY)?;
+        let res = table.get(key)?;
+        match res {
+            Some(v) => Ok(Some(serde_json::from_str(v.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn cache_token_security(&self, key: &str, cached: &Cached) -> shared::Result<()> {
+        let value = serde_json::to_string(cached)?;
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(TOKEN_SECURITY)?;
+            table.insert(key, value.as_str())?;
         }
         tx.commit()?;
         Ok(())
     }
 
-    pub fn unblock_address(&self, contract_addr: &str) -> shared::Result<bool> {
+    /// Adds the pool to the watchlist (or updates its note).
+    pub fn watch(&self, watched: &Watched) -> shared::Result<()> {
+        let value = serde_json::to_string(watched)?;
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(WATCHLIST)?;
+            let key = Watched::key(&watched.network, &watched.pool_address);
+            table.insert(key.as_str(), value.as_str())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn unwatch(&self, network: &str, pool_address: &str) -> shared::Result<bool> {
         let exists;
         let tx = self.db.begin_write()?;
         {
-            let mut table = tx.open_table(IGNORED_ADDRS)?;
-            let res = table.remove(contract_addr)?;
+            let mut table = tx.open_table(WATCHLIST)?;
+            let res = table.remove(Watched::key(network, pool_address).as_str())?;
             exists = res.is_some();
         }
         tx.commit()?;
         Ok(exists)
     }
+
+    /// Returns the watched pools (of all networks, if unspecified).
+    pub fn watchlist(&self, network: Option<&str>) -> shared::Result<Vec<Watched>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(WATCHLIST)?;
+        let mut pools = vec![];
+        for res in table.range::<&str>(..)? {
+            let (_, v) = res?;
+            let watched: Watched = serde_json::from_str(v.value())?;
+            if network.map(|n| n == watched.network).unwrap_or(true) {
+                pools.push(watched);
+            }
+        }
+        Ok(pools)
+    }
+
+    pub fn save_checkpoint(&self, checkpoint: &Checkpoint) -> shared::Result<()> {
+        let value = serde_json::to_string(checkpoint)?;
+        let tx = self.db.begin_write()?;
+        {
+            let mut table = tx.open_table(RUNNER_STATE)?;
+            table.insert(CHECKPOINT_KEY, value.as_str())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes and returns the saved checkpoint (if any).
+    pub fn take_checkpoint(&self) -> shared::Result<Option<Checkpoint>> {
+        let tx = self.db.begin_write()?;
+        let checkpoint = {
+            let mut table = tx.open_table(RUNNER_STATE)?;
+            let res = table.remove(CHECKPOINT_KEY)?;
+            match res {
+                Some(v) => Some(serde_json::from_str(v.value())?),
+                None => None,
+            }
+        };
+        tx.commit()?;
+        Ok(checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockKind, Blocked, Checkpoint, ScanCheckpoint, Storage, BLOCKLIST, META};
+    use chrono::{offset::Utc, TimeDelta};
+
+    #[test]
+    fn blocklist_scope_and_expiry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("blocklist.redb");
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        for addr in shared::IGNORED_POOLS {
+            assert!(storage.is_blocked("eth", BlockKind::Pool, addr).unwrap());
+        }
+
+        let mut scoped = Blocked::new(BlockKind::Pool, "pool1");
+        scoped.network = Some("solana".into());
+        let mut expired = Blocked::new(BlockKind::Token, "token1");
+        expired.expires_at = Some(Utc::now() - TimeDelta::hours(1));
+        let token = Blocked::new(BlockKind::Token, "token2");
+        let mut checksummed = Blocked::new(
+            BlockKind::Token,
+            "0xAbC000000000000000000000000000000000dEf1",
+        );
+        checksummed.network = Some("base".into());
+        storage
+            .block_all(&[scoped, expired, token, checksummed])
+            .unwrap();
+
+        let is_blocked = |n, k, a| storage.is_blocked(n, k, a).unwrap();
+        assert!(is_blocked("solana", BlockKind::Pool, "pool1"));
+        assert!(!is_blocked("base", BlockKind::Pool, "pool1"));
+        assert!(!is_blocked("solana", BlockKind::Token, "pool1"));
+        assert!(!is_blocked("solana", BlockKind::Token, "token1"));
+        assert!(is_blocked("base", BlockKind::Token, "token2"));
+        // imports are normalized like the lookups
+        let addr = "0xabc000000000000000000000000000000000def1";
+        assert!(is_blocked("base", BlockKind::Token, addr));
+
+        let total = storage.blocked(0, usize::MAX).unwrap().0;
+        assert_eq!(storage.purge_expired_blocks().unwrap(), total - 1);
+        assert!(storage.unblock(BlockKind::Token, None, "token2").unwrap());
+        assert!(!is_blocked("base", BlockKind::Token, "token2"));
+
+        // the entries of each network (and the one of all networks) are kept apart
+        let mut base = Blocked::new(BlockKind::Pool, "pool1");
+        base.network = Some("base".into());
+        storage
+            .block_all(&[base, Blocked::new(BlockKind::Pool, "pool1")])
+            .unwrap();
+        assert!(storage
+            .unblock(BlockKind::Pool, Some("solana"), "pool1")
+            .unwrap());
+        assert!(is_blocked("solana", BlockKind::Pool, "pool1"));
+        assert!(storage.unblock(BlockKind::Pool, None, "pool1").unwrap());
+        assert!(!is_blocked("solana", BlockKind::Pool, "pool1"));
+        assert!(is_blocked("base", BlockKind::Pool, "pool1"));
+
+        // seeding happens only once (per address)
+        let addr = shared::IGNORED_POOLS[0];
+        storage.unblock(BlockKind::Pool, None, addr).unwrap();
+        drop(storage);
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        assert!(!storage.is_blocked("eth", BlockKind::Pool, addr).unwrap());
+        let added = "0x5eed00000000000000000000000000000000000A";
+        storage.seed(&[addr, added]).unwrap();
+        assert!(!storage.is_blocked("eth", BlockKind::Pool, addr).unwrap());
+        assert!(storage
+            .is_blocked("eth", BlockKind::Pool, &added.to_lowercase())
+            .unwrap());
+    }
+
+    #[test]
+    fn migrate_unscoped_addresses() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("migrate.redb");
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        let total = storage.blocked(0, usize::MAX).unwrap().0;
+        let raw = "0xAbC000000000000000000000000000000000dEf1";
+        {
+            // as stored by v1 (without normalizing or scoping the key)
+            let tx = storage.db.begin_write().unwrap();
+            {
+                let value = serde_json::to_string(&Blocked::new(BlockKind::Token, raw)).unwrap();
+                let mut table = tx.open_table(BLOCKLIST).unwrap();
+                table
+                    .insert(format!("token_{}", raw).as_str(), value.as_str())
+                    .unwrap();
+                let mut meta = tx.open_table(META).unwrap();
+                meta.insert(super::SCHEMA_VERSION_KEY, 1).unwrap();
+            }
+            tx.commit().unwrap();
+        }
+        drop(storage);
+
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        let addr = raw.to_lowercase();
+        assert!(storage.is_blocked("base", BlockKind::Token, &addr).unwrap());
+        assert!(!storage.is_blocked("base", BlockKind::Token, raw).unwrap());
+        assert!(storage.unblock(BlockKind::Token, None, &addr).unwrap());
+        assert_eq!(storage.blocked(0, usize::MAX).unwrap().0, total);
+    }
+
+    #[test]
+    fn checkpoint_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("checkpoint.redb");
+        let storage = Storage::new(path.to_str().unwrap()).unwrap();
+        assert!(storage.take_checkpoint().unwrap().is_none());
+
+        let req = shared::Request {
+            network: "solana".into(),
+            pool_address: "pool1".into(),
+            sources: vec![("gt-top".into(), 3)],
+            watched: true,
+            ..Default::default()
+        };
+        storage
+            .save_checkpoint(&Checkpoint {
+                saved_at: Utc::now(),
+                scans: vec![ScanCheckpoint {
+                    network: "solana".into(),
+                    page: 4,
+                    ended_feeds: vec![false, true, false],
+                    pools: vec!["pool1".into(), "pool2".into()],
+                }],
+                buffer: vec![req.into()],
+            })
+            .unwrap();
+
+        let c = storage.take_checkpoint().unwrap().unwrap();
+        assert_eq!(c.scans[0].page, 4);
+        assert_eq!(c.scans[0].pools.len(), 2);
+        let req = shared::Request::from(c.buffer.into_iter().next().unwrap());
+        assert_eq!(req.pool_address, "pool1");
+        assert_eq!(req.sources, vec![("gt-top".to_string(), 3)]);
+        assert!(req.watched);
+        assert!(storage.take_checkpoint().unwrap().is_none());
+    }
 }